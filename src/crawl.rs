@@ -1,25 +1,93 @@
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::iter;
 use {Battleplan, load_plan};
+use diag::Diagnostic;
 use errors::*;
 use url::Url;
-use gh::models::IssueFromJson;
+use gh::models::{CommentFromJson, IssueFromJson, PullRequestFromJson};
 use regex::Regex;
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum UrlFact {
-    CrawlError(String),
+    CrawlError(CrawlError),
     GitHubIssue(IssueFromJson),
-    GitHubPullRequest,
+    GitHubPullRequest(PullRequestFromJson),
+    GitHubComments(Vec<CommentFromJson>),
+    // The crawler found a link from this URL to another
+    LinksTo(Url),
+    // The URL redirects to another canonical URL, e.g. because the
+    // issue was transferred or the repo renamed
+    MovedTo(Url),
+}
+
+#[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct CrawlError {
+    pub kind: CrawlErrorKind,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum CrawlErrorKind {
+    NotFound,
+    Gone,
+    // The client follows redirects, so this is never an error from a
+    // request, only a seed found to have a `MovedTo` fact
+    Moved,
+    RateLimited,
+    ParseFailure,
+    UnsupportedUrl,
+    Other,
+}
+
+impl CrawlErrorKind {
+    fn from_error(e: &Error) -> CrawlErrorKind {
+        match *e.kind() {
+            ErrorKind::HttpStatus(404) => CrawlErrorKind::NotFound,
+            ErrorKind::HttpStatus(410) => CrawlErrorKind::Gone,
+            ErrorKind::RateLimited => CrawlErrorKind::RateLimited,
+            ErrorKind::Deserialize(_) => CrawlErrorKind::ParseFailure,
+            ErrorKind::UnsupportedUrl(_) => CrawlErrorKind::UnsupportedUrl,
+            _ => CrawlErrorKind::Other,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            CrawlErrorKind::NotFound => "not-found",
+            CrawlErrorKind::Gone => "gone",
+            CrawlErrorKind::Moved => "moved",
+            CrawlErrorKind::RateLimited => "rate-limited",
+            CrawlErrorKind::ParseFailure => "parse-failure",
+            CrawlErrorKind::UnsupportedUrl => "unsupported-url",
+            CrawlErrorKind::Other => "other",
+        }
+    }
+}
+
+/// A crawl error as written to `gen/crawl-errors.yml`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CrawlErrorReport {
+    pub url: Url,
+    pub seed: bool,
+    pub kind: CrawlErrorKind,
+    pub message: String,
+}
+
+pub struct CrawlConfig {
+    pub default_max_distance: Distance,
+    pub fail_on_seed_errors: bool,
 }
 
 impl UrlFact {
     fn short(&self) -> String {
         match *self {
-            UrlFact::CrawlError(ref e) => format!("crawl error: {}", e),
+            UrlFact::CrawlError(ref e) => format!("crawl error ({}): {}", e.kind.name(), e.message),
             UrlFact::GitHubIssue(_) => format!("is a GitHub issue"),
-            UrlFact::GitHubPullRequest => format!("is a GitHub pull request"),
+            UrlFact::GitHubPullRequest(_) => format!("is a GitHub pull request"),
+            UrlFact::GitHubComments(ref c) => format!("has {} comments", c.len()),
+            UrlFact::LinksTo(ref u) => format!("links to {}", u),
+            UrlFact::MovedTo(ref u) => format!("moved to {}", u),
         }
     }
 }
@@ -28,6 +96,10 @@ pub type UrlFacts = HashMap<Url, HashSet<UrlFact>>;
 
 pub trait FactSetExt {
     fn gh_issue(&self) -> Option<&IssueFromJson>;
+    fn gh_pull(&self) -> Option<&PullRequestFromJson>;
+    fn gh_comments(&self) -> Option<&[CommentFromJson]>;
+    fn moved_to(&self) -> Option<&Url>;
+    fn links(&self) -> Vec<&Url>;
 }
 
 impl FactSetExt for HashSet<UrlFact> {
@@ -41,34 +113,145 @@ impl FactSetExt for HashSet<UrlFact> {
 
         None
     }
+
+    fn gh_pull(&self) -> Option<&PullRequestFromJson> {
+        for fact in self {
+            match *fact {
+                UrlFact::GitHubPullRequest(ref p) => return Some(p),
+                _ => ()
+            }
+        }
+
+        None
+    }
+
+    fn gh_comments(&self) -> Option<&[CommentFromJson]> {
+        for fact in self {
+            match *fact {
+                UrlFact::GitHubComments(ref c) => return Some(c),
+                _ => ()
+            }
+        }
+
+        None
+    }
+
+    fn moved_to(&self) -> Option<&Url> {
+        for fact in self {
+            match *fact {
+                UrlFact::MovedTo(ref u) => return Some(u),
+                _ => ()
+            }
+        }
+
+        None
+    }
+
+    fn links(&self) -> Vec<&Url> {
+        let mut links = Vec::new();
+        for fact in self {
+            match *fact {
+                UrlFact::LinksTo(ref u) => links.push(u),
+                _ => ()
+            }
+        }
+
+        links
+    }
 }
 
-pub fn crawl() -> Result<()> {
+pub fn crawl(config: CrawlConfig) -> Result<()> {
     let plan = load_plan()?;
     plan.validate()?;
 
-    let urls = initial_urls_from_plan(&plan);
-
-    let urls_with_distances = urls
-        .into_iter()
-        .zip(iter::repeat(0))
-        .collect::<Vec<_>>();
+    let urls = initial_urls_from_plan(&plan, config.default_max_distance);
+    let seeds: Vec<Url> = urls.iter().map(|u| u.0.clone()).collect();
 
-    let mut urls = VecDeque::from(urls_with_distances);
+    let mut urls = VecDeque::from(urls);
 
     let mut facts: UrlFacts = HashMap::new();
+    let mut errors = Vec::new();
+    // Issues link to each other, so each URL is only fetched once. Goals
+    // crawl to different depths, though, so a URL reached again with
+    // more distance left to go has its links followed further.
+    let mut remaining: HashMap<Url, Distance> = HashMap::new();
 
     while let Some(url) = urls.pop_front() {
-        if url.1 > MAX_DISTANCE { continue }
+        if url.1 > url.2 { continue }
+        let left = url.2 - url.1;
+        match remaining.get(&url.0).cloned() {
+            Some(prev) if prev >= left => continue,
+            Some(_) => {
+                remaining.insert(url.0.clone(), left);
+                let links: Vec<Url> = facts.get(&url.0)
+                    .map(|f| f.links().into_iter().cloned().collect())
+                    .unwrap_or(Vec::new());
+                for link in links {
+                    urls.push_back((link, url.1 + 1, url.2));
+                }
+                continue;
+            }
+            None => { remaining.insert(url.0.clone(), left); }
+        }
         match learn_about_url(&url, &mut urls, &mut facts) {
-            Ok(_) => (),
+            Ok(_) => {
+                // Parts of it, like its comments, may still have failed
+                let partial: Vec<CrawlError> = facts.get(&url.0).map(|f| {
+                    f.iter().filter_map(|fact| match *fact {
+                        UrlFact::CrawlError(ref e) => Some(e.clone()),
+                        _ => None,
+                    }).collect()
+                }).unwrap_or(Vec::new());
+                for e in partial {
+                    errors.push(CrawlErrorReport {
+                        url: url.0.clone(),
+                        seed: url.1 == 0,
+                        kind: e.kind,
+                        message: e.message,
+                    });
+                }
+            }
             Err(e) => {
-                add_fact(&mut facts, &url.0, UrlFact::CrawlError(format!("{}", e)));
+                let error = CrawlError {
+                    kind: CrawlErrorKind::from_error(&e),
+                    message: format!("{}", e),
+                };
+                errors.push(CrawlErrorReport {
+                    url: url.0.clone(),
+                    seed: url.1 == 0,
+                    kind: error.kind,
+                    message: error.message.clone(),
+                });
+                add_fact(&mut facts, &url.0, UrlFact::CrawlError(error));
+            }
+        }
+    }
+
+    // Moved seeds crawled fine, but the plan should be updated to
+    // their new URLs
+    for seed in &seeds {
+        if let Some(new_url) = facts.get(seed).and_then(|f| f.moved_to()) {
+            if !errors.iter().any(|e| e.url == *seed && e.kind == CrawlErrorKind::Moved) {
+                errors.push(CrawlErrorReport {
+                    url: seed.clone(),
+                    seed: true,
+                    kind: CrawlErrorKind::Moved,
+                    message: format!("moved to {}", new_url),
+                });
             }
         }
     }
 
     write_url_facts(&facts)?;
+    write_crawl_errors(&errors)?;
+    print_error_summary(&errors);
+
+    let seed_errors = errors.iter()
+        .filter(|e| e.seed && e.kind != CrawlErrorKind::Moved)
+        .count();
+    if config.fail_on_seed_errors && seed_errors > 0 {
+        return Err(format!("{} seed URLs failed to crawl", seed_errors).into());
+    }
 
     Ok(())
 }
@@ -77,25 +260,72 @@ fn write_url_facts(facts: &UrlFacts) -> Result<()> {
     super::write_yaml("crawl", &facts)
 }
 
+fn write_crawl_errors(errors: &[CrawlErrorReport]) -> Result<()> {
+    super::write_yaml("crawl-errors", &errors)
+}
+
+fn print_error_summary(errors: &[CrawlErrorReport]) {
+    if errors.is_empty() {
+        println!("crawl completed without errors");
+        return;
+    }
+
+    let mut counts = BTreeMap::new();
+    for e in errors {
+        *counts.entry(e.kind).or_insert(0) += 1;
+    }
+
+    println!("crawl errors:");
+    println!("");
+    println!("{:<16} {:>5}", "kind", "count");
+    for (kind, count) in counts {
+        println!("{:<16} {:>5}", kind.name(), count);
+    }
+    println!("");
+    println!("{:<16} {:<5} {}", "kind", "seed", "url");
+    for e in errors {
+        let seed = if e.seed { "yes" } else { "no" };
+        println!("{:<16} {:<5} {}", e.kind.name(), seed, e.url);
+    }
+}
+
 pub fn load_url_facts() -> Result<UrlFacts> {
     super::load_yaml("crawl")
 }
 
-fn initial_urls_from_plan(plan: &Battleplan) -> Vec<Url> {
+/// Returns the seed URLs for every goal, each paired with its starting
+/// distance (0) and the maximum distance to crawl out to from it.
+/// Seeds the crawler can't follow, like forum threads, are skipped.
+fn initial_urls_from_plan(plan: &Battleplan,
+                          default_max_distance: Distance)
+                          -> Vec<(Url, Distance, Distance)> {
     let mut urls = Vec::new();
     for goal in &plan.goals {
-        match Url::parse(&goal.tracking_link) {
-            Ok(url) => urls.push(url),
-            Err(_) => (/* bogus link */),
+        let max_distance = goal.crawl_depth.unwrap_or(default_max_distance);
+        let links = iter::once(&goal.tracking_link).chain(goal.seed_links.iter());
+        for link in links {
+            match Url::parse(link) {
+                Ok(ref url) if !is_crawlable(url) => {
+                    info!("not crawling {}", url);
+                }
+                Ok(url) => urls.push((url, 0, max_distance)),
+                Err(_) => (/* bogus link */),
+            }
         }
     }
 
     urls
 }
 
-const MAX_DISTANCE: u32 = 5;
+/// Whether the crawler knows how to learn about the URL. Only GitHub
+/// issues and pull requests are crawled.
+fn is_crawlable(url: &Url) -> bool {
+    url.host_str() == Some("github.com") && parse_gh_issue(url).is_ok()
+}
+
+pub const DEFAULT_MAX_DISTANCE: Distance = 5;
 
-type Distance = u32;
+pub type Distance = u32;
 
 fn add_fact(facts: &mut HashMap<Url, HashSet<UrlFact>>,
             url: &Url,
@@ -110,40 +340,46 @@ fn add_fact(facts: &mut HashMap<Url, HashSet<UrlFact>>,
     url_facts.insert(fact);
 }
 
-fn learn_about_url(url_d: &(Url, Distance),
-                   urls: &mut VecDeque<(Url, Distance)>,
+fn learn_about_url(url_d: &(Url, Distance, Distance),
+                   urls: &mut VecDeque<(Url, Distance, Distance)>,
                    facts: &mut HashMap<Url, HashSet<UrlFact>>) -> Result<()> {
     info!("learning about {}", url_d.0);
 
     let url = &url_d.0;
 
     if url.as_str().starts_with("https://github.com") {
-        let (new_urls, new_facts) = learn_about_github_url(url)?;
+        let (new_urls, new_facts) = learn_about_github_url(url, url_d.1 == 0)?;
 
         for new_url in new_urls {
-            urls.push_back((new_url, url_d.1 + 1));
+            add_fact(facts, url, UrlFact::LinksTo(new_url.clone()));
+            urls.push_back((new_url, url_d.1 + 1, url_d.2));
         }
 
         for (new_url, new_fact) in new_facts {
             add_fact(facts, &new_url, new_fact);
         }
     } else {
-        error!("URL not understood: {}", url);
+        return Err(ErrorKind::UnsupportedUrl(url.to_string()).into());
     }
 
     Ok(())
 }
 
-fn learn_about_github_url(url: &Url) -> Result<(Vec<Url>, Vec<(Url, UrlFact)>)> {
-    if url.as_str().contains("/issues/") {
-        learn_about_github_issue(url)
+fn learn_about_github_url(url: &Url, seed: bool) -> Result<(Vec<Url>, Vec<(Url, UrlFact)>)> {
+    // Pull requests are issues too, and the issues API gives us the
+    // body and comments in the same shape
+    let path = url.path();
+    if path.contains("/issues/") || path.contains("/pull/") || path.contains("/pulls/") {
+        learn_about_github_issue(url, seed)
     } else {
-        error!("GitHub URL not understood: {}", url);
-        Ok((Vec::new(), Vec::new()))
+        Err(ErrorKind::UnsupportedUrl(url.to_string()).into())
     }
 }
 
-fn learn_about_github_issue(url: &Url) -> Result<(Vec<Url>, Vec<(Url, UrlFact)>)> {
+/// Seeds are followed to every issue they mention. Other issues are
+/// only followed to their task lists' sub-issues, since their other
+/// references are mostly to unrelated work.
+fn learn_about_github_issue(url: &Url, seed: bool) -> Result<(Vec<Url>, Vec<(Url, UrlFact)>)> {
         
     use gh::client::Client;
 
@@ -158,50 +394,209 @@ fn learn_about_github_issue(url: &Url) -> Result<(Vec<Url>, Vec<(Url, UrlFact)>)
 
     new_facts.push((url.clone(), UrlFact::GitHubIssue(issue.clone())));
 
+    if let Some(canonical_url) = moved_issue_url(url, &issue) {
+        new_facts.push((url.clone(), UrlFact::MovedTo(canonical_url)));
+    }
+
+    // The issue's merge state is only available from the pulls API.
+    // Failing to get it or the comments shouldn't lose the issue.
+    if let Some(ref pr_urls) = issue.pull_request {
+        match client.fetch_pull_request(pr_urls) {
+            Ok(pull) => new_facts.push((url.clone(), UrlFact::GitHubPullRequest(pull))),
+            Err(e) => new_facts.push((url.clone(), crawl_error_fact(&e))),
+        }
+    }
+
+    match client.fetch_comments(&issue.comments_url) {
+        Ok(comments) => new_facts.push((url.clone(), UrlFact::GitHubComments(comments))),
+        Err(e) => new_facts.push((url.clone(), crawl_error_fact(&e))),
+    }
+
     let (more_urls, more_facts) = learn_about_rfcs_from_issue(&issue)?;
     new_urls.extend(more_urls);
     new_facts.extend(more_facts);
 
+    if let Some(ref body) = issue.body {
+        if seed {
+            new_urls.extend(parse_issue_links(&org, &repo, body));
+        } else {
+            new_urls.extend(parse_task_links(&org, &repo, body));
+        }
+    }
+
     Ok((new_urls, new_facts))
 }
 
+fn crawl_error_fact(e: &Error) -> UrlFact {
+    UrlFact::CrawlError(CrawlError {
+        kind: CrawlErrorKind::from_error(e),
+        message: format!("{}", e),
+    })
+}
+
 fn learn_about_rfcs_from_issue(issue: &IssueFromJson)
                                -> Result<(Vec<Url>, Vec<(Url, UrlFact)>)> {
     let mut new_urls = Vec::new();
 
     if let Some(ref body) = issue.body {
-        // Match "rust-lang/rfcs#more-than-one-digit"
-        let rfc_ref_re = Regex::new(r"rust-lang/rfcs#(\d{1,})").expect("");
+        for rfc_number in parse_rfc_numbers(body) {
+            let rfc_url = Url::parse(&format!("https://github.com/rust-lang/rfcs/pull/{}", rfc_number)).expect("");
+            new_urls.push(rfc_url);
+        }
+    }
 
-        let mut rfc_numbers = vec!();
-        
-        for line in body.lines() {
-            if let Some(cap) = rfc_ref_re.captures(line) {
-                rfc_numbers.push(cap.at(1).expect(""));
+    Ok((new_urls, Vec::new()))
+}
+
+/// Finds the issues and pull requests linked from the body of an issue
+/// in `org/repo`, e.g. sub-tasks like "- [x] implement `?` - #31954",
+/// or related issues like "mentoring instructions in #34000". RFCs are
+/// left to `learn_about_rfcs_from_issue`.
+pub fn parse_issue_links(org: &str, repo: &str, body: &str) -> Vec<Url> {
+    // Matches "#123", "org/repo#123" and GitHub issue and PR URLs, but
+    // not HTML entities like "&#39;" or anchors like "page.html#2"
+    let ref_re = Regex::new(r"(?:^|[^\w&#/])(?:([\w.-]+)/([\w.-]+))?#(\d+)\b").expect("");
+    let url_re = Regex::new(r"https://github.com/([^/\s]+)/([^/\s]+)/(?:issues|pull)/(\d+)").expect("");
+
+    let mut urls = Vec::new();
+
+    for line in body.lines() {
+        let refs = url_re.captures_iter(line).chain(ref_re.captures_iter(line));
+        for cap in refs {
+            let link_org = cap.at(1).unwrap_or(org);
+            let link_repo = cap.at(2).unwrap_or(repo);
+            let number = cap.at(3).expect("");
+            if link_org == "rust-lang" && link_repo == "rfcs" {
+                continue;
+            }
+            let url = format!("https://github.com/{}/{}/issues/{}", link_org, link_repo, number);
+            let url = Url::parse(&url).expect("");
+            if !urls.contains(&url) {
+                urls.push(url);
             }
         }
+    }
 
-        for rfc_number in rfc_numbers {
-            let rfc_url = Url::parse(&format!("https://github.com/rust-lang/rfcs/pulls/{}", rfc_number)).expect("");
-            new_urls.push(rfc_url);
+    urls
+}
+
+/// A markdown task list item, bulleted with `*`, `-` or `+`, or
+/// numbered like `1.` or `1)`. Captures its indentation, bullet,
+/// check mark and description. Shared with ponder, so the sub-issues
+/// crawled are the tasks it shows.
+pub static TASK_PATTERN: &'static str = r"^(\s*)([*+-]|\d+[.)]) +\[(.)\] +(.*)";
+
+/// Finds the issues and pull requests linked from the task list items
+/// of an issue in `org/repo`, e.g. "- [x] implement `?` - #31954"
+pub fn parse_task_links(org: &str, repo: &str, body: &str) -> Vec<Url> {
+    let task_re = Regex::new(TASK_PATTERN).expect("");
+
+    let mut urls = Vec::new();
+    for line in body.lines() {
+        if let Some(cap) = task_re.captures(line) {
+            for url in parse_issue_links(org, repo, cap.at(4).expect("")) {
+                if !urls.contains(&url) {
+                    urls.push(url);
+                }
+            }
         }
     }
 
-    Ok((new_urls, Vec::new()))
+    urls
 }
 
+/// Returns the issue's canonical URL if it differs from the one we
+/// requested. GitHub redirects requests for transferred issues and
+/// renamed repos, so the issue's own `html_url` is the only reliable
+/// record of where it lives now.
+fn moved_issue_url(url: &Url, issue: &IssueFromJson) -> Option<Url> {
+    let canonical_url = match issue.html_url.as_ref().map(|u| Url::parse(u)) {
+        Some(Ok(u)) => u,
+        _ => return None,
+    };
+
+    // `issues/N`, `pull/N` and `pulls/N` are the same item, so only
+    // compare where it lives and its number
+    let normalize = |u: &Url| {
+        parse_gh_issue(u).ok().map(|(org, repo, number)| {
+            (org.to_lowercase(), repo.to_lowercase(), number)
+        })
+    };
+
+    let (old, new) = match (normalize(url), normalize(&canonical_url)) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return None,
+    };
+
+    if old != new {
+        Some(canonical_url)
+    } else {
+        None
+    }
+}
+
+/// Warns about plan links that the last crawl found to have moved,
+/// suggesting the canonical URL to use instead.
+pub fn check_moved_links(plan: &Battleplan) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    let facts = match load_url_facts() {
+        Ok(f) => f,
+        Err(_) => return diags /* no crawl data yet */,
+    };
+
+    for goal in &plan.goals {
+        let links = iter::once(("tracking-link", &goal.tracking_link))
+            .chain(goal.seed_links.iter().map(|l| ("seed-links", l)));
+        for (field, link) in links {
+            let url = match Url::parse(link) {
+                Ok(u) => u,
+                Err(_) => continue,
+            };
+            if let Some(new_url) = facts.get(&url).and_then(|f| f.moved_to()) {
+                diags.push(Diagnostic::warning(
+                    goal.src.field_loc(field), "moved-link", "goal", goal.id.clone(),
+                    format!("goal {} links to {}, which has moved; update it to {}",
+                            goal.id, url, new_url))
+                           .with_fix(goal.src.field_lines(field), link, new_url.as_str()));
+            }
+        }
+    }
 
-fn parse_gh_issue(url: &Url) -> Result<(String, String, String)> {
-    // Parse "/$org/$repo/issues/$number" from URL
-    let re = Regex::new("/(.*)/(.*)/issues/(.*)").expect("");
+    diags
+}
+
+pub fn parse_gh_issue(url: &Url) -> Result<(String, String, String)> {
+    // Parse "/$org/$repo/issues/$number" or "/$org/$repo/pull/$number"
+    // from URL
+    let re = Regex::new(r"^/([^/]+)/([^/]+)/(issues|pull|pulls)/(\d+)").expect("");
     let path = url.path();
 
     if let Some(cap) = re.captures(path) {
         let org = cap.at(1).expect("");
         let repo = cap.at(2).expect("");
-        let number = cap.at(3).expect("");
+        let number = cap.at(4).expect("");
         Ok((org.into(), repo.into(), number.into()))
     } else {
-        Err(format!("can't parse GitHub issue url {}", url).into())
+        Err(ErrorKind::UnsupportedUrl(url.to_string()).into())
     }
 }
+
+/// Finds every RFC mentioned in `text`, in order of first mention
+pub fn parse_rfc_numbers(text: &str) -> Vec<u32> {
+    // Match "rust-lang/rfcs#more-than-one-digit" or the PR URL
+    let rfc_re = Regex::new(r"rust-lang/rfcs(#|/pull/)(\d+)").expect("");
+    let mut rfc_numbers = vec!();
+
+    for cap in rfc_re.captures_iter(text) {
+        let rfc_num_str = cap.at(2).expect("");
+        if let Ok(n) = str::parse(rfc_num_str) {
+            if !rfc_numbers.contains(&n) {
+                rfc_numbers.push(n);
+            }
+        } else {
+            warn!("weird rfc number didn't parse {}", rfc_num_str);
+        }
+    }
+
+    rfc_numbers
+}
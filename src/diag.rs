@@ -0,0 +1,517 @@
+//! Problems found in the battleplan, located in the YAML files they
+//! came from
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use regex::Regex;
+use serde_json;
+use errors::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub file: String,
+    // 1-based
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    // A short, stable name for the kind of problem, e.g. `bogus-theme`
+    pub code: &'static str,
+    // The kind and id of the plan object at fault. Objects that failed
+    // to load before their id was read are identified by their index.
+    pub obj_type: &'static str,
+    pub obj_id: String,
+    pub message: String,
+    // A mechanical correction `rsz check --fix` can make
+    pub fix: Option<Fix>,
+}
+
+/// Replaces the first `old` within `lines` with `new`. Neither may
+/// span lines.
+#[derive(Clone, Debug)]
+pub struct Fix {
+    // The first and last line to look in, 1-based
+    pub lines: (usize, usize),
+    pub old: String,
+    pub new: String,
+}
+
+impl Diagnostic {
+    pub fn error(location: Location, code: &'static str,
+                 obj_type: &'static str, obj_id: String,
+                 message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            location: location,
+            code: code,
+            obj_type: obj_type,
+            obj_id: obj_id,
+            message: message,
+            fix: None,
+        }
+    }
+
+    pub fn warning(location: Location, code: &'static str,
+                   obj_type: &'static str, obj_id: String,
+                   message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(location, code, obj_type, obj_id, message)
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn with_fix(mut self, lines: (usize, usize), old: &str, new: &str) -> Diagnostic {
+        self.fix = Some(Fix {
+            lines: lines,
+            old: old.to_string(),
+            new: new.to_string(),
+        });
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}[{}]: {}",
+               self.location, self.severity.name(), self.code, self.message)
+    }
+}
+
+/// How `rsz check` prints diagnostics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    // `file:line:col: error[code]: message`, like a compiler
+    Human,
+    // A JSON array of diagnostics
+    Json,
+    // GitHub Actions workflow commands, which GitHub shows as
+    // annotations on pull requests
+    GitHub,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "human" => Some(Format::Human),
+            "json" => Some(Format::Json),
+            "github" => Some(Format::GitHub),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DiagnosticJson<'a> {
+    severity: &'a str,
+    code: &'a str,
+    object_type: &'a str,
+    id: &'a str,
+    message: &'a str,
+    fixable: bool,
+    file: &'a str,
+    line: usize,
+    col: usize,
+}
+
+/// Prints diagnostics in file order, and fails if any are errors
+pub fn report(diags: &[Diagnostic], format: Format) -> Result<()> {
+    let mut diags: Vec<&Diagnostic> = diags.iter().collect();
+    diags.sort_by_key(|d| (d.location.file.clone(), d.location.line, d.location.col));
+
+    let errors = diags.iter().filter(|d| d.severity == Severity::Error).count();
+    let warnings = diags.len() - errors;
+
+    match format {
+        Format::Human => {
+            for diag in &diags {
+                println!("{}", diag);
+            }
+            if !diags.is_empty() {
+                println!("{} errors, {} warnings", errors, warnings);
+            }
+        }
+        Format::Json => {
+            let json: Vec<_> = diags.iter().map(|d| DiagnosticJson {
+                severity: d.severity.name(),
+                code: d.code,
+                object_type: d.obj_type,
+                id: &d.obj_id,
+                message: &d.message,
+                fixable: d.fix.is_some(),
+                file: &d.location.file,
+                line: d.location.line,
+                col: d.location.col,
+            }).collect();
+            let s = serde_json::to_string_pretty(&json)
+                .chain_err(|| "encoding diagnostics")?;
+            println!("{}", s);
+        }
+        Format::GitHub => {
+            for diag in &diags {
+                let title = format!("{} {} {}", diag.code, diag.obj_type, diag.obj_id);
+                println!("::{} file={},line={},col={},title={}::{}",
+                         diag.severity.name(), escape_github_property(&diag.location.file),
+                         diag.location.line, diag.location.col,
+                         escape_github_property(&title),
+                         escape_github(&diag.message));
+            }
+        }
+    }
+
+    if errors == 0 {
+        Ok(())
+    } else {
+        Err(format!("invalid battleplan: {} errors", errors).into())
+    }
+}
+
+/// Applies the diagnostics' fixes to their files, printing each
+/// change to stderr, and returns the diagnostics that are left
+pub fn apply_fixes(diags: Vec<Diagnostic>) -> Result<Vec<Diagnostic>> {
+    let mut left = Vec::new();
+    let mut by_file: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+    for diag in diags {
+        if diag.fix.is_some() {
+            by_file.entry(diag.location.file.clone()).or_insert(Vec::new()).push(diag);
+        } else {
+            left.push(diag);
+        }
+    }
+
+    for (file, diags) in by_file {
+        let mut text = String::new();
+        File::open(&file)?.read_to_string(&mut text)?;
+        let mut changed = false;
+
+        for diag in diags {
+            let fixed = {
+                let fix = diag.fix.as_ref().expect("");
+                let (first, last) = fix.lines;
+                let line_start = |n: usize| -> usize {
+                    text.split('\n').take(n - 1).map(|l| l.len() + 1).sum::<usize>()
+                };
+                let start = cmp::min(line_start(first), text.len());
+                let end = cmp::min(line_start(last.saturating_add(1)), text.len());
+                match text[start..end].find(&fix.old[..]) {
+                    Some(i) => {
+                        let i = start + i;
+                        // stderr, to keep the report parseable
+                        let _ = writeln!(io::stderr(), "{}: fixed: '{}' -> '{}'",
+                                         diag.location, fix.old, fix.new);
+                        Some(format!("{}{}{}", &text[..i], fix.new, &text[i + fix.old.len()..]))
+                    }
+                    // Another fix to the same text got there first
+                    None if text[start..end].contains(&fix.new[..]) => None,
+                    // The text isn't where the diagnostic is, so leave
+                    // it to be fixed by hand
+                    None => {
+                        left.push(diag.clone());
+                        None
+                    }
+                }
+            };
+            if let Some(fixed) = fixed {
+                text = fixed;
+                changed = true;
+            }
+        }
+
+        if changed {
+            let mut f = File::create(&file)?;
+            f.write_all(text.as_bytes())?;
+        }
+    }
+
+    Ok(left)
+}
+
+/// Workflow command data can't contain newlines
+fn escape_github(s: &str) -> String {
+    s.replace("%", "%25").replace("\r", "%0D").replace("\n", "%0A")
+}
+
+/// Workflow command properties also can't contain the `,` and `:`
+/// that separate them
+fn escape_github_property(s: &str) -> String {
+    escape_github(s).replace(",", "%2C").replace(":", "%3A")
+}
+
+/// Where each top-level item of a plan file, and each of its fields,
+/// starts. yaml-rust doesn't keep positions, so this scans the text
+/// for the block-style lists the plan files are written in.
+pub struct SourceMap {
+    file: String,
+    items: Vec<ItemSource>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ItemSource {
+    file: String,
+    line: usize,
+    col: usize,
+    // The item's last line
+    end_line: usize,
+    fields: BTreeMap<String, (usize, usize)>,
+}
+
+impl SourceMap {
+    pub fn scan(path: &Path, text: &str) -> SourceMap {
+        let item_re = Regex::new(r"^-(\s|$)").expect("");
+        let key_re = Regex::new(r"^(\s*(?:-\s+)?)([\w-]+)\s*:").expect("");
+        let file = path.strip_prefix(".").unwrap_or(path).display().to_string();
+
+        let mut items: Vec<ItemSource> = Vec::new();
+        // The column the current item's keys start at
+        let mut key_col = None;
+
+        for (i, line) in text.lines().enumerate() {
+            let lineno = i + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if trimmed == "---" && !items.is_empty() {
+                break;
+            }
+
+            if item_re.is_match(line) {
+                items.push(ItemSource {
+                    file: file.clone(),
+                    line: lineno,
+                    col: 1,
+                    end_line: lineno,
+                    fields: BTreeMap::new(),
+                });
+                key_col = None;
+            }
+
+            let item = match items.last_mut() {
+                Some(item) => item,
+                None => continue,
+            };
+            item.end_line = lineno;
+            if let Some(cap) = key_re.captures(line) {
+                let col = cap.at(1).expect("").len() + 1;
+                let key = cap.at(2).expect("");
+                if key_col.is_none() {
+                    key_col = Some(col);
+                }
+                if key_col == Some(col) && !item.fields.contains_key(key) {
+                    item.fields.insert(key.to_string(), (lineno, col));
+                }
+            }
+        }
+
+        SourceMap {
+            file: file,
+            items: items,
+        }
+    }
+
+    /// The source of the i'th top-level item, or the top of the file
+    /// if the scan didn't find it
+    pub fn item(&self, i: usize) -> ItemSource {
+        self.items.get(i).cloned().unwrap_or_else(|| ItemSource {
+            file: self.file.clone(),
+            line: 1,
+            col: 1,
+            end_line: usize::max_value(),
+            fields: BTreeMap::new(),
+        })
+    }
+}
+
+impl ItemSource {
+    pub fn loc(&self) -> Location {
+        Location {
+            file: self.file.clone(),
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    /// The location of a field, or of the item if it doesn't have one
+    pub fn field_loc(&self, field: &str) -> Location {
+        match self.fields.get(field) {
+            Some(&(line, col)) => Location {
+                file: self.file.clone(),
+                line: line,
+                col: col,
+            },
+            None => self.loc(),
+        }
+    }
+
+    /// The lines a field's value can be on: from its key to the next
+    /// field, or the whole item if it doesn't have the field
+    pub fn field_lines(&self, field: &str) -> (usize, usize) {
+        let start = match self.fields.get(field) {
+            Some(&(line, _)) => line,
+            None => return (self.line, self.end_line),
+        };
+        let end = self.fields.values()
+            .map(|&(line, _)| line)
+            .filter(|&line| line > start)
+            .min()
+            .map(|line| line - 1)
+            .unwrap_or(self.end_line);
+
+        (start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Location, SourceMap, apply_fixes};
+    use std::env;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::path::Path;
+
+    static GOALS: &'static str = "\
+# Goals
+- id: first
+  goal: The first goal
+  tracking-link: http://github.com/rust-lang/rust/issues/1
+  seed-links:
+    - http://github.com/rust-lang/rust/pulls/2
+  nested:
+    key: value
+
+- id: second
+  tracking-link: http://github.com/rust-lang/rust/issues/1
+";
+
+    /// Writes `text` to a fresh file in the temp dir, returning its path
+    fn temp_file(name: &str, text: &str) -> String {
+        let path = env::temp_dir().join(format!("rsz-test-{}.yml", name));
+        File::create(&path).unwrap().write_all(text.as_bytes()).unwrap();
+        path.display().to_string()
+    }
+
+    fn read(path: &str) -> String {
+        let mut text = String::new();
+        File::open(path).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    fn warning(file: &str, line: usize, code: &'static str) -> Diagnostic {
+        let loc = Location { file: file.to_string(), line: line, col: 3 };
+        Diagnostic::warning(loc, code, "goal", "x".to_string(), code.to_string())
+    }
+
+    #[test]
+    fn scan_finds_items_and_fields() {
+        let map = SourceMap::scan(Path::new("./goals.yml"), GOALS);
+
+        let first = map.item(0);
+        assert_eq!(first.loc().to_string(), "goals.yml:2:1");
+        assert_eq!(first.field_loc("id").line, 2);
+        assert_eq!(first.field_loc("id").col, 3);
+        assert_eq!(first.field_loc("tracking-link").line, 4);
+        assert_eq!(first.field_loc("seed-links").line, 5);
+        // Nested keys aren't the item's fields
+        assert_eq!(first.field_loc("key").line, 2);
+        assert_eq!(first.field_lines("seed-links"), (5, 6));
+        // The last field runs to the item's last line, not the blank
+        // line after it
+        assert_eq!(first.field_lines("nested"), (7, 8));
+        assert_eq!(first.field_lines("pitch"), (2, 8));
+
+        let second = map.item(1);
+        assert_eq!(second.field_loc("tracking-link").line, 11);
+        assert_eq!(second.field_lines("tracking-link"), (11, 11));
+
+        // Items the scan didn't find fall back to the whole file
+        let missing = map.item(2);
+        assert_eq!(missing.loc().to_string(), "goals.yml:1:1");
+        assert_eq!(missing.field_lines("id"), (1, usize::max_value()));
+    }
+
+    #[test]
+    fn fixes_only_look_within_their_lines() {
+        let file = temp_file("fix-window", GOALS);
+        let map = SourceMap::scan(Path::new(&file), GOALS);
+        let src = map.item(1);
+        let old = "http://github.com/rust-lang/rust/issues/1";
+        let new = "https://github.com/rust-lang/rust/issues/1";
+        let diag = warning(&file, 11, "http-link")
+            .with_fix(src.field_lines("tracking-link"), old, new);
+
+        let left = apply_fixes(vec![diag]).unwrap();
+
+        assert!(left.is_empty());
+        let text = read(&file);
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[3], "  tracking-link: http://github.com/rust-lang/rust/issues/1");
+        assert_eq!(lines[10], "  tracking-link: https://github.com/rust-lang/rust/issues/1");
+    }
+
+    #[test]
+    fn fixes_missing_from_their_lines_are_left() {
+        let file = temp_file("fix-missing", GOALS);
+        let diag = warning(&file, 2, "http-link")
+            .with_fix((2, 3), "http://github.com/rust-lang/rust/issues/1", "https://example.com");
+
+        let left = apply_fixes(vec![diag]).unwrap();
+
+        assert_eq!(left.len(), 1);
+        assert_eq!(read(&file), GOALS);
+    }
+
+    #[test]
+    fn fixes_to_the_same_link_dont_conflict() {
+        let file = temp_file("fix-same-link", GOALS);
+        let map = SourceMap::scan(Path::new(&file), GOALS);
+        let lines = map.item(0).field_lines("seed-links");
+        let old = "http://github.com/rust-lang/rust/pulls/2";
+        let fixed = "https://github.com/rust-lang/rust/pull/2";
+        let moved = "https://github.com/rust-lang/cargo/issues/3";
+        let diags = vec![
+            warning(&file, 5, "http-link").with_fix(lines, old, fixed),
+            warning(&file, 5, "pulls-link").with_fix(lines, old, fixed),
+            warning(&file, 5, "moved-link").with_fix(lines, old, moved),
+        ];
+
+        let left = apply_fixes(diags).unwrap();
+
+        // The second identical fix is already applied, and the moved
+        // link no longer matches, so it's left to fix by hand
+        let codes: Vec<_> = left.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["moved-link"]);
+        let text = read(&file);
+        assert_eq!(text.lines().nth(5), Some("    - https://github.com/rust-lang/rust/pull/2"));
+        assert!(!text.contains(moved));
+    }
+}
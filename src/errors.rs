@@ -20,6 +20,17 @@ error_chain! {
         FileNotFound {
             description("file not found")
         }
+        RateLimited {
+            description("GitHub API rate limit exceeded")
+        }
+        Deserialize(what: String) {
+            description("failed to deserialize response")
+            display("failed to deserialize {}", what)
+        }
+        UnsupportedUrl(url: String) {
+            description("URL not understood")
+            display("URL not understood: {}", url)
+        }
     }
 }
 
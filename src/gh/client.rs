@@ -11,6 +11,7 @@ use chrono::{DateTime, UTC};
 use hyper;
 use hyper::client::{RedirectPolicy, Response};
 use hyper::header::Headers;
+use hyper::status::StatusCode;
 use serde::Deserialize;
 use serde_json;
 
@@ -33,6 +34,10 @@ header! { (Link, "Link") => [String] }
 
 const PER_PAGE: u32 = 100;
 
+// The reactions preview media type makes issues and pull requests
+// include their reaction counts
+const ACCEPT: &'static str = "application/vnd.github.squirrel-girl-preview";
+
 #[derive(Debug)]
 pub struct Client {
     token: String,
@@ -113,6 +118,15 @@ impl Client {
         self.get_models(&url, &params)
     }
 
+    /// Fetches every comment on an issue or pull request, given the
+    /// issue's `comments_url`.
+    pub fn fetch_comments(&self, comments_url: &str) -> Result<Vec<CommentFromJson>> {
+        let mut params = ParameterMap::new();
+        params.insert("per_page", format!("{}", PER_PAGE));
+
+        self.get_models(comments_url, &params)
+    }
+
     fn get_models<M: Deserialize>(&self,
                                   start_url: &str,
                                   params: &ParameterMap)
@@ -124,7 +138,7 @@ impl Client {
         try!(res.read_to_string(&mut buf));
 
         let mut models = serde_json::from_str::<Vec<M>>(&buf)
-                              .chain_err(|| "deserializing models")?;
+                              .chain_err(|| ErrorKind::Deserialize("models".into()))?;
 
         let mut next_url = Self::next_page(&res.headers);
         while next_url.is_some() {
@@ -135,7 +149,7 @@ impl Client {
             try!(next_res.read_to_string(&mut buf));
 
             models.extend(serde_json::from_str::<Vec<M>>(&buf)
-                          .chain_err(|| "deserializing models")?);
+                          .chain_err(|| ErrorKind::Deserialize("models".into()))?);
 
             next_url = Self::next_page(&next_res.headers);
         }
@@ -152,7 +166,7 @@ impl Client {
             try!(res.read_to_string(&mut buf));
 
             Ok(serde_json::from_str::<PullRequestFromJson>(&buf)
-               .chain_err(|| "deserializing pr")?)
+               .chain_err(|| ErrorKind::Deserialize("pr".into()))?)
         } else {
             Err("FIXME".into())
         }
@@ -167,7 +181,7 @@ impl Client {
         res.read_to_string(&mut buf)?;
 
         Ok(serde_json::from_str::<IssueFromJson>(&buf)
-           .chain_err(|| "deserializing isse")?)
+           .chain_err(|| ErrorKind::Deserialize("issue".into()))?)
     }
 
     fn next_page(h: &Headers) -> Option<String> {
@@ -218,14 +232,30 @@ impl Client {
         // Rate limit
         sleep(Duration::from_millis(DELAY));
 
-        client
+        let res = client
             .get(&url)
             //.header(Auth(format!("token {}", &self.token)))
             .header(UA(self.ua.clone()))
             .header(TZ("UTC".to_string()))
-            .header(Accept("application/vnd.github.v3".to_string()))
+            .header(Accept(ACCEPT.to_string()))
             .header(hyper::header::Connection::close())
             .send()
-            .chain_err(|| "http error")
+            .chain_err(|| "http error")?;
+
+        if res.status.is_success() {
+            return Ok(res);
+        }
+
+        // GitHub reports an exhausted rate limit as a 403 with no
+        // requests remaining
+        let exhausted = res.headers.get::<RateLimitRemaining>()
+            .map(|r| **r == 0)
+            .unwrap_or(false);
+        if res.status == StatusCode::TooManyRequests
+            || (res.status == StatusCode::Forbidden && exhausted) {
+            return Err(ErrorKind::RateLimited.into());
+        }
+
+        Err(ErrorKind::HttpStatus(res.status.to_u16() as u32).into())
     }
 }
@@ -45,7 +45,7 @@ pub struct Issue {
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
 pub struct IssueComment {
-    pub id: i32,
+    pub id: i64,
     pub fk_issue: i32,
     pub fk_user: i32,
     pub body: String,
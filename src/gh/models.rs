@@ -49,17 +49,34 @@ impl MilestoneFromJson {
 
 #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub struct LabelFromJson {
-    name: String,
-    color: String,
+    pub name: String,
+    pub color: String,
 }
 
 pub type PullRequestUrls = BTreeMap<String, String>;
 
+/// Reaction counts, only present when requested with the reactions
+/// preview media type
+#[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
+pub struct ReactionsFromJson {
+    pub total_count: i32,
+    #[serde(rename="+1")]
+    pub plus_one: i32,
+    #[serde(rename="-1")]
+    pub minus_one: i32,
+    pub laugh: i32,
+    pub hooray: i32,
+    pub confused: i32,
+    pub heart: i32,
+}
+
 #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub struct IssueFromJson {
     pub number: i32,
     pub user: GitHubUser,
     pub assignee: Option<GitHubUser>,
+    // Every assignee, including `assignee`
+    pub assignees: Option<Vec<GitHubUser>>,
     pub state: String,
     pub title: String,
     pub body: Option<String>,
@@ -72,9 +89,28 @@ pub struct IssueFromJson {
     pub created_at: DateTime<UTC>,
     pub updated_at: DateTime<UTC>,
     pub comments_url: String,
+    // The canonical URL of the issue, after any transfers or renames
+    pub html_url: Option<String>,
+    pub reactions: Option<ReactionsFromJson>,
 }
 
 impl IssueFromJson {
+    pub fn has_label(&self, name: &str) -> bool {
+        match self.labels {
+            Some(ref labels) => labels.iter().any(|l| l.name == name),
+            None => false,
+        }
+    }
+
+    /// Everyone assigned to the issue. Older data only has the single
+    /// `assignee`.
+    pub fn all_assignees(&self) -> Vec<&GitHubUser> {
+        match self.assignees {
+            Some(ref assignees) => assignees.iter().collect(),
+            None => self.assignee.iter().collect(),
+        }
+    }
+
     pub fn with_repo(self, repo: &str) -> (Issue, Option<Milestone>) {
         let milestone_id = match self.milestone {
             Some(ref m) => Some(m.id),
@@ -108,9 +144,10 @@ impl IssueFromJson {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub struct CommentFromJson {
-    pub id: i32,
+    // Comment ids have outgrown i32
+    pub id: i64,
     pub html_url: String,
     pub body: String,
     pub user: GitHubUser,
@@ -149,7 +186,7 @@ impl CommentFromJson {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub struct PullRequestFromJson {
     pub number: i32,
     pub review_comments_url: String,
@@ -167,6 +204,7 @@ pub struct PullRequestFromJson {
     pub additions: i32,
     pub deletions: i32,
     pub changed_files: i32,
+    pub reactions: Option<ReactionsFromJson>,
 }
 
 impl PullRequestFromJson {
@@ -25,8 +25,14 @@ use yaml::{YamlLoader, Yaml};
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::io::Read;
-use std::collections::BTreeMap;
-use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::iter;
+use url::Url;
+use std::result::Result as StdResult;
+use chrono::NaiveDate;
+use regex::Regex;
+use serde::{Serialize, Deserialize, Deserializer, de};
+use serde_json::Value as JsonValue;
 use std::fs;
 use std::io::Write;
 
@@ -34,7 +40,12 @@ mod errors;
 use errors::*;
 
 mod crawl;
+mod diag;
 mod ponder;
+mod schema;
+
+use diag::{Diagnostic, ItemSource, SourceMap};
+use schema::{Schema, SchemaType, Type, required, optional};
 
 mod gh {
     pub mod client;
@@ -53,6 +64,7 @@ fn main() {
         for e in e.iter().skip(1) {
             error!("cause: {}", e);
         }
+        std::process::exit(1);
     }
 }
 
@@ -60,9 +72,10 @@ fn main_() -> Result<()> {
     let config = read_args()?;
 
     match config {
-        Config::Check => validate_plan()?,
-        Config::Crawl => crawl::crawl()?,
-        Config::Ponder => ponder::ponder()?,
+        Config::Check(config) => validate_plan(config)?,
+        Config::Schema(dir) => schema::write_schemas(&dir, &plan_schemas())?,
+        Config::Crawl(config) => crawl::crawl(config)?,
+        Config::Ponder(config) => ponder::ponder(config)?,
         _ => panic!()
     }
 
@@ -74,66 +87,208 @@ fn read_args() -> Result<Config> {
 
     let matches = App::new("Battleplan Rust Command Console")
         .setting(AppSettings::DeriveDisplayOrder)
-        .subcommand(SubCommand::with_name("check"))
-        .subcommand(SubCommand::with_name("crawl"))
-        .subcommand(SubCommand::with_name("ponder"))
+        .subcommand(SubCommand::with_name("check")
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["human", "json", "github"])
+                         .help("How to print diagnostics: human, json, or github annotations"))
+                    .arg(Arg::with_name("strict")
+                         .long("strict")
+                         .help("Treat placeholder content and bad links as errors"))
+                    .arg(Arg::with_name("fix")
+                         .long("fix")
+                         .help("Rewrite the plan files to fix mechanical problems")))
+        .subcommand(SubCommand::with_name("schema")
+                    .arg(Arg::with_name("out-dir")
+                         .long("out-dir")
+                         .takes_value(true)
+                         .help("Where to write the JSON Schemas (default: schema)")))
+        .subcommand(SubCommand::with_name("crawl")
+                    .arg(Arg::with_name("max-depth")
+                         .long("max-depth")
+                         .takes_value(true)
+                         .help("Default crawl depth for goals that don't set `crawl-depth`"))
+                    .arg(Arg::with_name("fail-on-seed-errors")
+                         .long("fail-on-seed-errors")
+                         .help("Exit with an error if any seed URL fails to crawl")))
+        .subcommand(SubCommand::with_name("ponder")
+                    .arg(Arg::with_name("hotness-window")
+                         .long("hotness-window")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("Length in days of a window to measure hotness over"))
+                    .arg(Arg::with_name("stale-days")
+                         .long("stale-days")
+                         .takes_value(true)
+                         .help("Days without activity before a goal is stale"))
+                    .arg(Arg::with_name("super-stale-days")
+                         .long("super-stale-days")
+                         .takes_value(true)
+                         .help("Days without activity before a goal is super-stale"))
+                    .arg(Arg::with_name("roster-exclude")
+                         .long("roster-exclude")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1)
+                         .help("A bot or other account to leave off contributor rosters"))
+                    .arg(Arg::with_name("tasks-from-comments")
+                         .long("tasks-from-comments")
+                         .help("Include task lists posted in comments by tracking issue maintainers")))
         .subcommand(SubCommand::with_name("compare"))
         .subcommand(SubCommand::with_name("merge"))
         .subcommand(SubCommand::with_name("triage"))
         .subcommand(SubCommand::with_name("discover"))
         .get_matches();
 
-    match matches.subcommand_name() {
-        Some("check") => Ok(Config::Check),
-        Some("crawl") => Ok(Config::Crawl),
-        Some("ponder") => Ok(Config::Ponder),
-        Some("compare") => Ok(Config::Compare),
-        Some("merge") => Ok(Config::Merge),
-        Some(_) |
-        None => Ok(Config::Check),
+    match matches.subcommand() {
+        ("check", Some(m)) => {
+            let format = match m.value_of("format") {
+                Some(f) => diag::Format::from_name(f)
+                    .ok_or(Error::from(format!("bogus --format '{}'", f)))?,
+                None => diag::Format::Human,
+            };
+            Ok(Config::Check(CheckConfig {
+                format: format,
+                strict: m.is_present("strict"),
+                fix: m.is_present("fix"),
+            }))
+        }
+        ("schema", Some(m)) => {
+            let dir = m.value_of("out-dir").unwrap_or(SCHEMA_DIR);
+            Ok(Config::Schema(dir.to_string()))
+        }
+        ("crawl", Some(m)) => {
+            let max_distance = match m.value_of("max-depth") {
+                Some(d) => str::parse(d)
+                    .chain_err(|| format!("bogus --max-depth '{}'", d))?,
+                None => crawl::DEFAULT_MAX_DISTANCE,
+            };
+            Ok(Config::Crawl(crawl::CrawlConfig {
+                default_max_distance: max_distance,
+                fail_on_seed_errors: m.is_present("fail-on-seed-errors"),
+            }))
+        }
+        ("ponder", Some(m)) => {
+            let hotness_windows = match m.values_of("hotness-window") {
+                Some(ws) => {
+                    let mut windows = Vec::new();
+                    for w in ws {
+                        windows.push(str::parse(w)
+                                     .chain_err(|| format!("bogus --hotness-window '{}'", w))?);
+                    }
+                    windows
+                }
+                None => ponder::DEFAULT_HOTNESS_WINDOWS.to_vec(),
+            };
+            let stale_days = match m.value_of("stale-days") {
+                Some(d) => str::parse(d)
+                    .chain_err(|| format!("bogus --stale-days '{}'", d))?,
+                None => ponder::DEFAULT_STALE_DAYS,
+            };
+            let super_stale_days = match m.value_of("super-stale-days") {
+                Some(d) => str::parse(d)
+                    .chain_err(|| format!("bogus --super-stale-days '{}'", d))?,
+                None => ponder::DEFAULT_SUPER_STALE_DAYS,
+            };
+            Ok(Config::Ponder(ponder::PonderConfig {
+                hotness_windows: hotness_windows,
+                staleness: ponder::StalenessThresholds {
+                    stale_days: stale_days,
+                    super_stale_days: super_stale_days,
+                },
+                roster_exclude: m.values_of("roster-exclude")
+                    .map(|vs| vs.map(|v| v.to_string()).collect())
+                    .unwrap_or(Vec::new()),
+                tasks_from_comments: m.is_present("tasks-from-comments"),
+            }))
+        }
+        ("compare", _) => Ok(Config::Compare),
+        ("merge", _) => Ok(Config::Merge),
+        _ => Ok(Config::Check(CheckConfig {
+            format: diag::Format::Human,
+            strict: false,
+            fix: false,
+        })),
     }
 }
 
 enum Config {
-    Check,
-    Crawl,
-    Ponder,
+    Check(CheckConfig),
+    Schema(String),
+    Crawl(crawl::CrawlConfig),
+    Ponder(ponder::PonderConfig),
     Compare,
     Merge,
 }
 
+struct CheckConfig {
+    format: diag::Format,
+    // Report content warnings as errors
+    strict: bool,
+    // Apply the diagnostics' fixes to the plan files
+    fix: bool,
+}
+
 static DATA_DIR: &'static str = "./_data";
+static SCHEMA_DIR: &'static str = "./schema";
 
-fn validate_plan() -> Result<()> {
+fn validate_plan(config: CheckConfig) -> Result<()> {
     let plan = load_plan()?;
 
-    plan.validate()
+    let mut diags = plan.diagnose();
+    diags.extend(crawl::check_moved_links(&plan));
+    diags.extend(ponder::check_release_goals(&plan));
+
+    if config.fix {
+        diags = diag::apply_fixes(diags)?;
+    }
+
+    if config.strict {
+        for d in &mut diags {
+            if CONTENT_CODES.contains(&d.code) {
+                d.severity = diag::Severity::Error;
+            }
+        }
+    }
+
+    diag::report(&diags, config.format)
 }
 
 fn load_plan() -> Result<Battleplan> {
     let data_dir = PathBuf::from(DATA_DIR);
-    let themes = yaml_from_file(&data_dir.join("themes.yml"))?;
-    let goals = yaml_from_file(&data_dir.join("goals.yml"))?;
-    let problems = yaml_from_file(&data_dir.join("problems.yml"))?;
-    let releases = yaml_from_file(&data_dir.join("releases.yml"))?;
-
-    let themes = themes_from_yaml(themes)?;
-    let goals = goals_from_yaml(goals)?;
-    let problems = problems_from_yaml(problems)?;
-    let releases = releases_from_yaml(releases)?;
+    let (themes, themes_src) = yaml_from_file(&data_dir.join("themes.yml"))?;
+    let (goals, goals_src) = yaml_from_file(&data_dir.join("goals.yml"))?;
+    let (problems, problems_src) = yaml_from_file(&data_dir.join("problems.yml"))?;
+    let (releases, releases_src) = yaml_from_file(&data_dir.join("releases.yml"))?;
+    let (pipelines, pipelines_src) = yaml_from_file(&data_dir.join("pipelines.yml"))?;
+    let (teams, teams_src) = yaml_from_file(&data_dir.join("teams.yml"))?;
+
+    let mut diags = Vec::new();
+    let themes = objects_from_yaml(themes, &themes_src, &mut diags)?;
+    let goals = objects_from_yaml(goals, &goals_src, &mut diags)?;
+    let problems = objects_from_yaml(problems, &problems_src, &mut diags)?;
+    let releases = objects_from_yaml(releases, &releases_src, &mut diags)?;
+    let pipelines = objects_from_yaml(pipelines, &pipelines_src, &mut diags)?;
+    let teams = objects_from_yaml(teams, &teams_src, &mut diags)?;
 
     Ok(Battleplan {
         themes: themes,
         goals: goals,
         problems: problems,
         releases: releases,
+        pipelines: pipelines,
+        teams: teams,
+        diagnostics: diags,
     })
 }
 
-fn yaml_from_file(path: &Path) -> Result<Vec<Yaml>> {
+fn yaml_from_file(path: &Path) -> Result<(Vec<Yaml>, SourceMap)> {
     let mut contents = String::new();
     File::open(path)?.read_to_string(&mut contents)?;
-    Ok(YamlLoader::load_from_str(&contents)?)
+    let yaml = YamlLoader::load_from_str(&contents)?;
+    Ok((yaml, SourceMap::scan(path, &contents)))
 }
 
 struct Battleplan {
@@ -141,159 +296,639 @@ struct Battleplan {
     goals: Vec<Goal>,
     problems: Vec<Problem>,
     releases: Vec<Release>,
+    pipelines: Vec<Pipeline>,
+    teams: Vec<Team>,
+    // Problems found while loading the plan
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A plan object, deserialized from one item of a plan file
+trait PlanObject: Deserialize {
+    fn type_name() -> &'static str;
+    fn schema() -> Schema;
+    fn set_src(&mut self, src: ItemSource);
+}
+
+/// Defines a plan object and its schema from one table of fields, each
+/// with its yaml key, whether it's required, and a description. Items
+/// are checked against the schema before they're deserialized, so
+/// serde only has to fill the fields in.
+macro_rules! plan_object {
+    ($(#[$attr:meta])*
+     struct $t:ident($name:expr, $file:expr, $desc:expr) {
+         $($field:ident: $ty:ty = $req:ident($key:tt, $field_desc:expr),)*
+     }) => {
+        $(#[$attr])*
+        #[derive(Deserialize)]
+        struct $t {
+            $(
+                #[serde(rename=$key, default)]
+                $field: $ty,
+            )*
+            // Where it is in its yaml file
+            #[serde(skip_deserializing)]
+            src: ItemSource,
+        }
+
+        impl PlanObject for $t {
+            fn type_name() -> &'static str { $name }
+
+            fn schema() -> Schema {
+                Schema {
+                    file: $file,
+                    desc: $desc,
+                    fields: vec![
+                        $($req($key, <$ty as SchemaType>::schema_type(), $field_desc),)*
+                    ],
+                }
+            }
+
+            fn set_src(&mut self, src: ItemSource) { self.src = src; }
+        }
+    }
+}
+
+plan_object! {
+    struct Theme("theme", "themes", "The major activities the project is engaged in") {
+        id: String = required("id", "Unique id, lowercase with hyphens and underscores"),
+        name: String = required("name", "Display name"),
+        team: String = required("team", "The id of the team responsible, from teams.yml"),
+        top: bool = optional("top", "Whether to show the theme in the default view"),
+        pitch: String = required("pitch", "Why the theme is important and exciting"),
+        stale_days: Option<u32> = optional(
+            "stale-days", "Days without activity before the theme's goals are stale"),
+        super_stale_days: Option<u32> = optional(
+            "super-stale-days", "Days without activity before the theme's goals are super-stale"),
+        pipeline: Option<String> = optional(
+            "pipeline", "The default pipeline kind of the theme's goals, from pipelines.yml"),
+    }
+}
+
+plan_object! {
+    struct Goal("goal", "goals", "Time-boxed initiatives to achieve a specific goal") {
+        id: String = required("id", "Unique id, lowercase with hyphens and underscores"),
+        goal: String = required("goal", "The deliverable, as a sentence"),
+        pitch: String = required("pitch", "Why people should want the goal"),
+        top: bool = optional("top", "Whether to show the goal in the default view"),
+        theme: String = required("theme", "The id of the goal's theme, from themes.yml"),
+        tracking_link: String = required("tracking-link", "The URL of the tracking issue"),
+        release: String = required(
+            "release", "The id of the targeted release, from releases.yml"),
+        seed_links: Vec<String> = optional(
+            "seed-links", "Extra URLs for `rsz crawl` to start from"),
+        crawl_depth: Option<u32> = optional(
+            "crawl-depth", "How many links away from the tracking and seed links to crawl"),
+        pipeline: Option<String> = optional(
+            "pipeline", "The goal's pipeline kind, from pipelines.yml, overriding its theme's"),
+        goal_deps: Vec<String> = optional(
+            "goal-deps", "The ids of goals that must be complete before this one can be"),
+    }
+}
+
+plan_object! {
+    struct Problem("problem", "problems", "Problems users have that themes address") {
+        id: String = required("id", "Unique id, lowercase with hyphens and underscores"),
+        pitch: String = required("pitch", "The problem"),
+        theme: String = required("theme", "The id of the theme that addresses it"),
+        goal_deps: Vec<String> = optional("goal-deps", "The ids of the goals that would solve it"),
+    }
+}
+
+plan_object! {
+    struct Team("team", "teams", "The Rust teams responsible for themes") {
+        id: String = required("id", "Unique id, lowercase with hyphens and underscores"),
+        name: String = required("name", "Display name"),
+        members: Vec<String> = optional("members", "GitHub logins"),
+        contact: String = required(
+            "contact", "Where to reach the team, e.g. a mailing list or forum category"),
+    }
+}
+
+plan_object! {
+    /// A kind of goal, e.g. language features or infrastructure, and the
+    /// stages its goals go through
+    struct Pipeline("pipeline", "pipelines", "Pipeline kinds, and the stages their goals go through") {
+        id: String = required("id", "Unique id, lowercase with hyphens and underscores"),
+        name: String = required("name", "Display name"),
+        stages: Vec<PipelineStageRule> = required("stages", "The stages, in order"),
+        complete_when: Vec<String> = optional(
+            "complete-when",
+            "The stages that must be complete for a goal to be complete; all of them if omitted"),
+    }
+}
+
+enum PipelineStageRule {
+    // The filed, FCP and accepted stages of every RFC
+    Rfcs,
+    TrackingIssueOpen,
+    // The task list of the tracking issue
+    TrackingTasks,
+    TrackingIssueClosed,
+    // Complete when the tracking issue has the label
+    Label { name: String, label: String },
+}
+
+impl PipelineStageRule {
+    /// The stages pipelines can name by id
+    fn built_ins() -> Vec<PipelineStageRule> {
+        vec![
+            PipelineStageRule::Rfcs,
+            PipelineStageRule::TrackingIssueOpen,
+            PipelineStageRule::TrackingTasks,
+            PipelineStageRule::TrackingIssueClosed,
+        ]
+    }
+
+    fn id(&self) -> &str {
+        match *self {
+            PipelineStageRule::Rfcs => "rfcs",
+            PipelineStageRule::TrackingIssueOpen => "tracking-issue-open",
+            PipelineStageRule::TrackingTasks => "tracking-tasks",
+            PipelineStageRule::TrackingIssueClosed => "tracking-issue-closed",
+            PipelineStageRule::Label { ref name, .. } => name,
+        }
+    }
+
+    fn from_id(id: &str) -> Option<PipelineStageRule> {
+        PipelineStageRule::built_ins().into_iter().find(|r| r.id() == id)
+    }
 }
 
-struct Theme {
-    id: String,
-    name: String,
-    team: String,
-    top: bool,
-    pitch: String
+impl SchemaType for PipelineStageRule {
+    fn schema_type() -> Type {
+        let ids = PipelineStageRule::built_ins().iter().map(|r| r.id().to_string()).collect();
+        let custom = Type::Object(vec![
+            required("name", Type::String, "The stage's name"),
+            required("label", Type::String, "The tracking issue label that completes the stage"),
+        ]);
+        Type::AnyOf(vec![Type::Enum(ids), custom])
+    }
 }
 
-struct Goal {
-    id: String,
-    goal: String,
-    pitch: String,
-    top: bool,
-    theme: String,
-    tracking_link: String,
-    release: String,
+/// Stages are either the id of a built-in stage, like
+/// `tracking-issue-closed`, or a map with a `name` and the `label`
+/// that marks it complete
+impl Deserialize for PipelineStageRule {
+    fn deserialize<D>(deserializer: &mut D) -> StdResult<PipelineStageRule, D::Error>
+        where D: Deserializer
+    {
+        deserializer.deserialize(StageRuleVisitor)
+    }
 }
 
-struct Problem {
-    id: String,
-    pitch: String,
-    theme: String,
+struct StageRuleVisitor;
+
+impl de::Visitor for StageRuleVisitor {
+    type Value = PipelineStageRule;
+
+    fn visit_str<E>(&mut self, id: &str) -> StdResult<PipelineStageRule, E>
+        where E: de::Error
+    {
+        PipelineStageRule::from_id(id)
+            .ok_or(E::invalid_value(&format!("unknown stage `{}`", id)))
+    }
+
+    fn visit_map<V>(&mut self, mut visitor: V) -> StdResult<PipelineStageRule, V::Error>
+        where V: de::MapVisitor
+    {
+        let mut name = None;
+        let mut label = None;
+        while let Some(key) = visitor.visit_key::<String>()? {
+            match &key[..] {
+                "name" => name = Some(visitor.visit_value()?),
+                "label" => label = Some(visitor.visit_value()?),
+                _ => return Err(de::Error::unknown_field(&key)),
+            }
+        }
+        visitor.end()?;
+
+        let name = match name {
+            Some(name) => name,
+            None => visitor.missing_field("name")?,
+        };
+        let label = match label {
+            Some(label) => label,
+            None => visitor.missing_field("label")?,
+        };
+
+        Ok(PipelineStageRule::Label { name: name, label: label })
+    }
+}
+
+/// Words that only turn up in lorem ipsum, and the markers people
+/// leave for things to fill in
+static PLACEHOLDER_PATTERN: &'static str =
+    r"(?i:\b(lorem|ipsum|dolor|amet|consectetur|adipiscing|pellentesque|phasellus|vestibulum)\b)|\b(TODO|TBD|FIXME|XXX)\b";
+
+/// Pitch lengths, in characters, that read well on the site
+const MIN_PITCH_LEN: usize = 50;
+const MAX_PITCH_LEN: usize = 1000;
+
+/// Makes the mechanical corrections to a link that `rsz check --fix`
+/// applies: https, and GitHub's `pull/` rather than `pulls/`
+fn fix_link(link: &str) -> String {
+    let link = if link.starts_with("http://") {
+        format!("https://{}", &link["http://".len()..])
+    } else {
+        link.to_string()
+    };
+    match link.find("/pulls/") {
+        Some(i) if link.starts_with("https://github.com/") => {
+            format!("{}/pull/{}", &link[..i], &link[i + "/pulls/".len()..])
+        }
+        _ => link,
+    }
+}
+
+/// The codes of diagnostics about content that isn't finished yet,
+/// which `rsz check --strict` treats as errors
+static CONTENT_CODES: &'static [&'static str] = &[
+    "placeholder", "pitch-length", "todo-link", "bad-url",
+];
+
+/// Used by goals whose goal and theme don't name a pipeline
+static DEFAULT_PIPELINE: &'static str = "language-feature";
+
+impl Battleplan {
+    /// The pipeline that applies to a goal
+    fn goal_pipeline(&self, goal: &Goal) -> Option<&Pipeline> {
+        let theme_pipeline = self.themes.iter()
+            .find(|t| t.id == goal.theme)
+            .and_then(|t| t.pipeline.as_ref());
+        let id = goal.pipeline.as_ref()
+            .or(theme_pipeline)
+            .map(|s| &s[..])
+            .unwrap_or(DEFAULT_PIPELINE);
+
+        self.pipelines.iter().find(|p| p.id == id)
+    }
 }
 
-struct Team {
-    id: String,
-    name: String,
+plan_object! {
+    struct Release("release", "releases", "Rust releases, oldest first") {
+        id: String = required(
+            "id", "The version, e.g. \"1.12\", or a placeholder like \"TBD\""),
+        future: bool = optional("future", "Whether goals may still target the release"),
+        date: Option<NaiveDate> = optional(
+            "date", "The day the release shipped or is expected to ship"),
+        stale_days: Option<u32> = optional(
+            "stale-days", "Days without activity before the release's goals are stale"),
+        super_stale_days: Option<u32> = optional(
+            "super-stale-days", "Days without activity before the release's goals are super-stale"),
+    }
 }
 
-struct Release {
-    id: String,
-    future: bool,
+impl Release {
+    /// The numeric parts of a version id like "1.12", or None for
+    /// placeholders like "TBD"
+    fn version(&self) -> Option<Vec<u32>> {
+        self.id.split('.').map(|p| p.parse().ok()).collect()
+    }
 }
 
 macro_rules! verr {
-    ($fmt:expr, $($arg:tt)*) => (warn!(concat!("validation error: ", $fmt), $($arg)*));
+    ($diags:expr, $loc:expr, $code:expr, $obj_type:expr, $obj_id:expr,
+     $fmt:expr, $($arg:tt)*) => (
+        $diags.push(Diagnostic::error($loc, $code, $obj_type, $obj_id.to_string(),
+                                      format!($fmt, $($arg)*)))
+    );
+}
+
+macro_rules! vwarn {
+    ($diags:expr, $loc:expr, $code:expr, $obj_type:expr, $obj_id:expr,
+     $fmt:expr, $($arg:tt)*) => (
+        $diags.push(Diagnostic::warning($loc, $code, $obj_type, $obj_id.to_string(),
+                                        format!($fmt, $($arg)*)))
+    );
 }
 
 impl Battleplan {
+    /// Prints the plan's problems, failing if any are errors
     fn validate(&self) -> Result<()> {
-        let mut good = true;
+        diag::report(&self.diagnose(), diag::Format::Human)
+    }
+
+    /// The problems found loading the plan, and those found checking
+    /// its cross-references
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        let mut diags = self.diagnostics.clone();
+
+        self.check_ids(&mut diags);
+        self.check_release_order(&mut diags);
+        self.check_content(&mut diags);
+        self.check_goal_deps(&mut diags);
 
         for goal in &self.goals {
             if !self.themes.iter().any(|x| x.id == goal.theme) {
-                good = false;
-                verr!("goal {} mentions bogus theme '{}'",
+                verr!(diags, goal.src.field_loc("theme"),
+                      "bogus-theme", "goal", goal.id,
+                      "goal {} mentions bogus theme '{}'",
                       goal.id, goal.theme);
             }
             if !self.releases.iter().any(|x| x.id == goal.release) {
-                good = false;
-                verr!("goal {} mentions bogus release '{}'",
+                verr!(diags, goal.src.field_loc("release"),
+                      "bogus-release", "goal", goal.id,
+                      "goal {} mentions bogus release '{}'",
                       goal.id, goal.release);
             }
-
-            if goal.tracking_link.starts_with("http://") {
-                verr!("goal {} has https tracking link: {}",
-                      goal.id, goal.tracking_link);
+        }
+        for goal in &self.goals {
+            if let Some(ref pipeline) = goal.pipeline {
+                if !self.pipelines.iter().any(|x| x.id == *pipeline) {
+                    verr!(diags, goal.src.field_loc("pipeline"),
+                          "bogus-pipeline", "goal", goal.id,
+                          "goal {} mentions bogus pipeline '{}'",
+                          goal.id, pipeline);
+                }
+                continue;
+            }
+            // A bogus theme pipeline is reported with the theme
+            let theme_pipeline = self.themes.iter()
+                .find(|t| t.id == goal.theme)
+                .and_then(|t| t.pipeline.as_ref());
+            if theme_pipeline.is_none() && self.goal_pipeline(goal).is_none() {
+                verr!(diags, goal.src.loc(), "no-pipeline", "goal", goal.id,
+                      "goal {} names no pipeline, and the default '{}' doesn't exist",
+                      goal.id, DEFAULT_PIPELINE);
+            }
+        }
+        for theme in &self.themes {
+            if !self.teams.iter().any(|x| x.id == theme.team) {
+                verr!(diags, theme.src.field_loc("team"),
+                      "bogus-team", "theme", theme.id,
+                      "theme {} mentions bogus team '{}'",
+                      theme.id, theme.team);
+            }
+            if let Some(ref pipeline) = theme.pipeline {
+                if !self.pipelines.iter().any(|x| x.id == *pipeline) {
+                    verr!(diags, theme.src.field_loc("pipeline"),
+                          "bogus-pipeline", "theme", theme.id,
+                          "theme {} mentions bogus pipeline '{}'",
+                          theme.id, pipeline);
+                }
+            }
+        }
+        for pipeline in &self.pipelines {
+            for stage in &pipeline.complete_when {
+                if !pipeline.stages.iter().any(|x| x.id() == stage) {
+                    verr!(diags, pipeline.src.field_loc("complete-when"),
+                          "bogus-stage", "pipeline", pipeline.id,
+                          "pipeline {} completes on bogus stage '{}'",
+                          pipeline.id, stage);
+                }
             }
         }
         for problem in &self.problems {
             if !self.themes.iter().any(|x| x.id == problem.theme) {
-                good = false;
-                verr!("problem {} mentions bogus theme '{}'",
+                verr!(diags, problem.src.field_loc("theme"),
+                      "bogus-theme", "problem", problem.id,
+                      "problem {} mentions bogus theme '{}'",
                       problem.id, problem.theme);
             }
         }
 
-        if good {
-            Ok(())
-        } else {
-            Err("invalid battleplan".into())
-        }
+        diags
     }
-}
 
-
-macro_rules! try_lookup_string {
-    ($map: expr, $field_name:expr, $obj_type:expr, $obj_id:expr) => {{
-        let field = lookup_string(&mut $map, $field_name);
-        if let Err(e) = field {
-            verr!("{} {}; {}", $obj_type, $obj_id, e);
-            continue;
+    /// Releases are listed oldest first, with placeholders like "TBD"
+    /// after all the versions
+    fn check_release_order(&self, diags: &mut Vec<Diagnostic>) {
+        let mut prev: Option<&Release> = None;
+        let mut prev_date: Option<&Release> = None;
+
+        for release in &self.releases {
+            if let Some(prev) = prev {
+                match (prev.version(), release.version()) {
+                    (Some(ref a), Some(ref b)) if a >= b => {
+                        verr!(diags, release.src.field_loc("id"),
+                              "release-order", "release", release.id,
+                              "release {} is listed after release {}",
+                              release.id, prev.id);
+                    }
+                    (None, Some(_)) => {
+                        verr!(diags, release.src.field_loc("id"),
+                              "release-order", "release", release.id,
+                              "release {} is listed after unversioned release {}",
+                              release.id, prev.id);
+                    }
+                    _ => (),
+                }
+            }
+            prev = Some(release);
+
+            if let Some(date) = release.date {
+                if let Some(prev) = prev_date {
+                    if prev.date.expect("") >= date {
+                        verr!(diags, release.src.field_loc("date"),
+                              "release-order", "release", release.id,
+                              "release {} is dated {}, no later than release {}",
+                              release.id, date, prev.id);
+                    }
+                }
+                prev_date = Some(release);
+            }
         }
+    }
 
-        let field = field.expect("");
+    /// Looks for lorem ipsum and TODOs that haven't been filled in yet,
+    /// and links that can't be crawled
+    fn check_content(&self, diags: &mut Vec<Diagnostic>) {
+        let mut texts: Vec<(&'static str, &str, &str, &ItemSource, &str)> = Vec::new();
+        for theme in &self.themes {
+            texts.push(("theme", &theme.id[..], "name", &theme.src, &theme.name[..]));
+            texts.push(("theme", &theme.id[..], "pitch", &theme.src, &theme.pitch[..]));
+        }
+        for goal in &self.goals {
+            texts.push(("goal", &goal.id[..], "goal", &goal.src, &goal.goal[..]));
+            texts.push(("goal", &goal.id[..], "pitch", &goal.src, &goal.pitch[..]));
+        }
+        for problem in &self.problems {
+            texts.push(("problem", &problem.id[..], "pitch", &problem.src, &problem.pitch[..]));
+        }
 
-        field
-    }}
-}
+        let placeholder_re = Regex::new(PLACEHOLDER_PATTERN).expect("");
+        for &(type_, id, field, src, text) in &texts {
+            if let Some((start, end)) = placeholder_re.find(text) {
+                vwarn!(diags, src.field_loc(field), "placeholder", type_, id,
+                       "{} {} has placeholder {}: '{}'",
+                       type_, id, field, &text[start..end]);
+            } else if field == "pitch" {
+                let len = text.trim().chars().count();
+                if len < MIN_PITCH_LEN || len > MAX_PITCH_LEN {
+                    vwarn!(diags, src.field_loc(field), "pitch-length", type_, id,
+                           "{} {} has a {}-character pitch; pitches should be {} to {}",
+                           type_, id, len, MIN_PITCH_LEN, MAX_PITCH_LEN);
+                }
+            }
+        }
 
-macro_rules! try_lookup_bool {
-    ($map: expr, $field_name:expr, $obj_type:expr, $obj_id:expr) => {{
-        let field = lookup_bool(&mut $map, $field_name);
-        if let Err(e) = field {
-            verr!("{} {}; {}", $obj_type, $obj_id, e);
-            continue;
+        for goal in &self.goals {
+            let links = iter::once(("tracking-link", &goal.tracking_link))
+                .chain(goal.seed_links.iter().map(|l| ("seed-links", l)));
+            for (field, link) in links {
+                let link = link.trim();
+                if link.is_empty() || link == "TODO" || link == "TBD" {
+                    vwarn!(diags, goal.src.field_loc(field), "todo-link", "goal", goal.id,
+                           "goal {} has no {} yet", goal.id, field);
+                    continue;
+                }
+                match Url::parse(link) {
+                    Ok(ref url) if url.scheme() == "http" || url.scheme() == "https" => {
+                        let fixed = fix_link(link);
+                        if url.scheme() == "http" {
+                            diags.push(Diagnostic::warning(
+                                goal.src.field_loc(field), "http-link", "goal", goal.id.clone(),
+                                format!("goal {} has http {}: {}", goal.id, field, link))
+                                       .with_fix(goal.src.field_lines(field), link, &fixed));
+                        }
+                        if url.host_str() == Some("github.com") && url.path().contains("/pulls/") {
+                            diags.push(Diagnostic::warning(
+                                goal.src.field_loc(field), "pulls-link", "goal", goal.id.clone(),
+                                format!("goal {} has a {} to pulls/ instead of pull/: {}",
+                                        goal.id, field, link))
+                                       .with_fix(goal.src.field_lines(field), link, &fixed));
+                        }
+                    }
+                    Ok(_) => {
+                        vwarn!(diags, goal.src.field_loc(field), "bad-url", "goal", goal.id,
+                               "goal {} has a {} that isn't a web link: {}",
+                               goal.id, field, link);
+                    }
+                    Err(e) => {
+                        vwarn!(diags, goal.src.field_loc(field), "bad-url", "goal", goal.id,
+                               "goal {} has an unparsable {}: {}: {}",
+                               goal.id, field, link, e);
+                    }
+                }
+            }
         }
-        let field = field.expect("");
+    }
 
-        field
-    }}
-}
+    /// Goal dependencies must name goals, and goals can't depend on
+    /// themselves, even indirectly
+    fn check_goal_deps(&self, diags: &mut Vec<Diagnostic>) {
+        let deps = self.goals.iter().map(|g| ("goal", &g.id, &g.goal_deps, &g.src))
+            .chain(self.problems.iter().map(|p| ("problem", &p.id, &p.goal_deps, &p.src)));
+        for (type_, id, goal_deps, src) in deps {
+            for dep in goal_deps {
+                if !self.goals.iter().any(|g| g.id == *dep) {
+                    verr!(diags, src.field_loc("goal-deps"),
+                          "bogus-goal-dep", type_, id,
+                          "{} {} depends on bogus goal '{}'",
+                          type_, id, dep);
+                }
+            }
+        }
 
-macro_rules! try_as_map {
-    ($yaml: expr, $obj_type:expr, $obj_id:expr) => {{
-        let map = $yaml.as_hash();
-        if map.is_none() {
-            verr!("{} {} is not a map", $obj_type, $obj_id);
-            continue;
+        // Depth-first search for back edges. Each cycle is reported
+        // once, at the goal the search entered it from.
+        let mut done: HashSet<&str> = HashSet::new();
+        for goal in &self.goals {
+            let mut path = Vec::new();
+            self.find_dep_cycles(goal, &mut path, &mut done, diags);
         }
-        let map = map.expect("");
+    }
 
-        map.clone()
-    }}
-}
+    fn find_dep_cycles<'a>(&'a self, goal: &'a Goal,
+                           path: &mut Vec<&'a str>,
+                           done: &mut HashSet<&'a str>,
+                           diags: &mut Vec<Diagnostic>) {
+        if done.contains(&goal.id[..]) {
+            return;
+        }
+        if let Some(i) = path.iter().position(|id| *id == goal.id) {
+            let mut cycle: Vec<&str> = path[i..].to_vec();
+            cycle.push(&goal.id);
+            let first = self.goals.iter().find(|g| g.id == cycle[0]).expect("");
+            verr!(diags, first.src.field_loc("goal-deps"),
+                  "dependency-cycle", "goal", first.id,
+                  "goal {} depends on itself: {}",
+                  first.id, cycle.join(" -> "));
+            return;
+        }
 
-fn lookup(y: &mut BTreeMap<Yaml, Yaml>, field_name: &str) -> Result<Yaml> {
-    let key = Yaml::String(field_name.to_string());
-    if let Some(y) = y.remove(&key) {
-        Ok(y)
-    } else {
-        Err(format!("missing field `{}`", field_name).into())
+        path.push(&goal.id);
+        for dep in &goal.goal_deps {
+            if let Some(dep) = self.goals.iter().find(|g| g.id == *dep) {
+                self.find_dep_cycles(dep, path, done, diags);
+            }
+        }
+        path.pop();
+        done.insert(&goal.id);
     }
-    
-}
 
-fn lookup_string(y: &mut BTreeMap<Yaml, Yaml>, field_name: &str) -> Result<String> {
-    let y = lookup(y, field_name)?;
-    if let Some(s) = y.as_str() {
-        Ok(s.to_string())
-    } else {
-        Err("not a string".into())
-    }
-}
+    /// Ids must be unique within their type and well-formed, and
+    /// shouldn't be shared between types, since the site links
+    /// objects of every type by id
+    fn check_ids(&self, diags: &mut Vec<Diagnostic>) {
+        let id_re = Regex::new(r"^[a-z0-9][a-z0-9_-]*$").expect("");
+
+        let mut ids: Vec<(&'static str, &str, &ItemSource)> = Vec::new();
+        ids.extend(self.themes.iter().map(|x| ("theme", &x.id[..], &x.src)));
+        ids.extend(self.goals.iter().map(|x| ("goal", &x.id[..], &x.src)));
+        ids.extend(self.problems.iter().map(|x| ("problem", &x.id[..], &x.src)));
+        ids.extend(self.pipelines.iter().map(|x| ("pipeline", &x.id[..], &x.src)));
+        ids.extend(self.teams.iter().map(|x| ("team", &x.id[..], &x.src)));
+        ids.extend(self.releases.iter().map(|x| ("release", &x.id[..], &x.src)));
+
+        for &(type_, id, src) in &ids {
+            if id.trim() != id {
+                diags.push(Diagnostic::warning(
+                    src.field_loc("id"), "id-whitespace", type_, id.to_string(),
+                    format!("{} id '{}' has surrounding whitespace", type_, id))
+                           .with_fix(src.field_lines("id"), id, id.trim()));
+            }
+        }
 
-fn lookup_bool(y: &mut BTreeMap<Yaml, Yaml>, field_name: &str) -> Result<bool> {
-    let y = lookup(y, field_name);
-    // Fields that don't exist are false
-    if y.is_err() { return Ok(false) };
-    let y = y.expect("");
+        // Compare ids as they'll be once fixed. Release ids are
+        // versions, and the site turns their dots into underscores.
+        let objs: Vec<(&'static str, String, &ItemSource)> = ids.iter().map(|&(type_, id, src)| {
+            let id = if type_ == "release" {
+                id.trim().replace(".", "_")
+            } else {
+                id.trim().to_string()
+            };
+            (type_, id, src)
+        }).collect();
+
+        let mut seen: HashMap<(&str, &str), &ItemSource> = HashMap::new();
+        for &(type_, ref id, src) in &objs {
+            if type_ != "release" && !id_re.is_match(id) {
+                verr!(diags, src.field_loc("id"), "bad-id", type_, id,
+                      "{} id '{}' must be lowercase letters, digits, hyphens and underscores",
+                      type_, id);
+            }
 
-    match y {
-        Yaml::Boolean(v) => {
-            Ok(v)
+            if let Some(first) = seen.get(&(type_, &id[..])) {
+                verr!(diags, src.field_loc("id"), "duplicate-id", type_, id,
+                      "{} {} is already defined at {}", type_, id, first.loc());
+                continue;
+            }
+            seen.insert((type_, &id[..]), src);
         }
-        _ => {
-            Err("not a bool".into())
+
+        // Pipelines and teams only appear on the site by name
+        let mut seen: HashMap<&str, &'static str> = HashMap::new();
+        let linked = objs.iter().filter(|o| o.0 != "pipeline" && o.0 != "team");
+        for &(type_, ref id, src) in linked {
+            match seen.get(&id[..]).cloned() {
+                Some(other) if other != type_ => {
+                    vwarn!(diags, src.field_loc("id"), "shared-id", type_, id,
+                           "{} {} has the same id as a {}", type_, id, other);
+                }
+                Some(_) => (),
+                None => { seen.insert(&id[..], type_); }
+            }
         }
     }
 }
 
+/// The schemas of every plan file, for `rsz schema`
+fn plan_schemas() -> Vec<Schema> {
+    vec![Theme::schema(), Goal::schema(), Problem::schema(),
+         Release::schema(), Pipeline::schema(), Team::schema()]
+}
+
 fn root_yaml_to_vec<'a>(y: &'a Vec<Yaml>, type_: &str) -> Result<&'a Vec<Yaml>> {
     let y = y.get(0)
         .ok_or(Error::from(format!("{} yaml has no elements", type_)))?;
@@ -303,112 +938,101 @@ fn root_yaml_to_vec<'a>(y: &'a Vec<Yaml>, type_: &str) -> Result<&'a Vec<Yaml>>
     Ok(y)
 }
 
-fn warn_extra_fields(y: BTreeMap<Yaml, Yaml>, type_: &str, id: &str) {
-    for (key, _) in y.into_iter() {
-        verr!("{} {} has extra field: {:?}", type_, id, key);
-    }
-}
-
-fn themes_from_yaml(y: Vec<Yaml>) -> Result<Vec<Theme>> {
-    let mut res = Vec::new();
-    let y = root_yaml_to_vec(&y, "theme")?;
-
-    for (i, y) in y.into_iter().enumerate() {
-        let mut map = try_as_map!(y, "theme", i);
-
-        let id = try_lookup_string!(map, "id", "theme", i);
-        let name = try_lookup_string!(map, "name", "theme", id);
-        let team = try_lookup_string!(map, "team", "theme", id);
-        let top = try_lookup_bool!(map, "top", "theme", id);
-        let pitch = try_lookup_string!(map, "pitch", "theme", id);
-
-        warn_extra_fields(map, "theme", &id);
-
-        res.push(Theme {
-            id: id,
-            name: name,
-            team: team,
-            top: top,
-            pitch: pitch,
-        });
-    }
-
-    Ok(res)
-}
-
-fn goals_from_yaml(y: Vec<Yaml>) -> Result<Vec<Goal>> {
+/// Checks each item of a plan file against its schema and
+/// deserializes it. Items that don't fit are reported and left out of
+/// the plan; unknown fields are only warned about.
+fn objects_from_yaml<T>(y: Vec<Yaml>, source: &SourceMap,
+                        diags: &mut Vec<Diagnostic>) -> Result<Vec<T>>
+    where T: PlanObject
+{
     let mut res = Vec::new();
-    let y = root_yaml_to_vec(&y, "goal")?;
-
-    for (i, y) in y.into_iter().enumerate() {
-        let mut map = try_as_map!(y, "goal", i);
-
-        let id = try_lookup_string!(map, "id", "goal", i);
-        let goal = try_lookup_string!(map, "goal", "goal", id);
-        let top = try_lookup_bool!(map, "top", "goal", id);
-        let pitch = try_lookup_string!(map, "pitch", "goal", id);
-        let theme = try_lookup_string!(map, "theme", "goal", id);
-        let tracking_link = try_lookup_string!(map, "tracking-link", "goal", id);
-        let release = try_lookup_string!(map, "release", "goal", id);
-
-        warn_extra_fields(map, "goal", &id);
+    let type_ = T::type_name();
+    let y = root_yaml_to_vec(&y, type_)?;
+
+    let schema = T::schema();
+
+    for (i, y) in y.iter().enumerate() {
+        let src = source.item(i);
+        // Name the object by its id if it has one
+        let id = y.as_hash()
+            .and_then(|m| m.get(&Yaml::String("id".to_string())))
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string())
+            .unwrap_or(i.to_string());
+        let json = yaml_to_json(y);
+
+        let mut valid = true;
+        for e in schema.check(&json) {
+            let loc = e.field.map(|f| src.field_loc(&f)).unwrap_or(src.loc());
+            // Unknown fields are ignored when deserializing, so they
+            // don't stop the item from loading
+            if e.code == "extra-field" {
+                vwarn!(diags, loc, e.code, type_, id, "{} {}; {}", type_, id, e.message);
+            } else {
+                verr!(diags, loc, e.code, type_, id, "{} {}; {}", type_, id, e.message);
+                valid = false;
+            }
+        }
+        if !valid {
+            continue;
+        }
 
-        res.push(Goal {
-            id: id,
-            goal: goal,
-            top: top,
-            pitch: pitch,
-            theme: theme,
-            tracking_link: tracking_link,
-            release: release,
-        });
+        match serde_json::from_value::<T>(json) {
+            Ok(mut obj) => {
+                obj.set_src(src);
+                res.push(obj);
+            }
+            Err(e) => {
+                let (code, field, msg) = describe_de_error(&e);
+                let loc = field.map(|f| src.field_loc(&f)).unwrap_or(src.loc());
+                verr!(diags, loc, code, type_, id, "{} {}; {}", type_, id, msg);
+            }
+        }
     }
 
     Ok(res)
 }
 
-fn problems_from_yaml(y: Vec<Yaml>) -> Result<Vec<Problem>> {
-    let mut res = Vec::new();
-    let y = root_yaml_to_vec(&y, "problem")?;
+/// The diagnostic code, the field at fault if known, and a message
+/// for a schema error
+fn describe_de_error(e: &serde_json::Error) -> (&'static str, Option<String>, String) {
+    use serde_json::error::ErrorCode;
 
-    for (i, y) in y.into_iter().enumerate() {
-        let mut map = try_as_map!(y, "problem", i);
-
-        let id = try_lookup_string!(map, "id", "problem", i);
-        let pitch = try_lookup_string!(map, "pitch", "problem", id);
-        let theme = try_lookup_string!(map, "theme", "problem", id);
-
-        warn_extra_fields(map, "problem", &id);
-
-        res.push(Problem {
-            id: id,
-            pitch: pitch,
-            theme: theme,
-        });
+    match *e {
+        serde_json::Error::Syntax(ErrorCode::MissingField(field), _, _) => {
+            ("bad-field", Some(field.to_string()),
+             format!("missing field `{}`", field))
+        }
+        serde_json::Error::Syntax(ErrorCode::UnknownField(ref field), _, _) => {
+            ("extra-field", Some(field.clone()),
+             format!("unknown field `{}`", field))
+        }
+        serde_json::Error::Syntax(ref code, _, _) => {
+            ("bad-field", None, code.to_string())
+        }
+        ref e => ("bad-field", None, e.to_string()),
     }
-
-    Ok(res)
 }
 
-fn releases_from_yaml(y: Vec<Yaml>) -> Result<Vec<Release>> {
-    let mut res = Vec::new();
-    let y = root_yaml_to_vec(&y, "release")?;
-
-    for (i, y) in y.into_iter().enumerate() {
-        let mut map = try_as_map!(y, "release", i);
-
-        let id = try_lookup_string!(map, "id", "release", i);
-        let future = try_lookup_bool!(map, "future", "release", id);
-
-        warn_extra_fields(map, "release", &id);
-
-        res.push(Release {
-            id: id,
-            future: future,
-        });
+/// serde_yaml can only deserialize from text, so plan items are
+/// converted to serde_json values to deserialize them one at a time
+fn yaml_to_json(y: &Yaml) -> JsonValue {
+    match *y {
+        Yaml::Real(ref s) => {
+            s.parse().map(JsonValue::F64).unwrap_or(JsonValue::String(s.clone()))
+        }
+        Yaml::Integer(i) => JsonValue::I64(i),
+        Yaml::String(ref s) => JsonValue::String(s.clone()),
+        Yaml::Boolean(b) => JsonValue::Bool(b),
+        Yaml::Array(ref a) => JsonValue::Array(a.iter().map(yaml_to_json).collect()),
+        Yaml::Hash(ref h) => {
+            // Plan files only use string keys
+            JsonValue::Object(h.iter().filter_map(|(k, v)| {
+                k.as_str().map(|k| (k.to_string(), yaml_to_json(v)))
+            }).collect())
+        }
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => JsonValue::Null,
     }
-
-    Ok(res)
 }
 
 fn write_yaml<T>(name: &str, value: T) -> Result<()>
@@ -445,3 +1069,77 @@ fn load_yaml<T>(name: &str) -> Result<T>
 
     Ok(value)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Battleplan, Goal, objects_from_yaml};
+    use diag::SourceMap;
+    use std::path::Path;
+    use yaml::YamlLoader;
+
+    /// A plan of goals with the given ids and dependencies, and nothing
+    /// else
+    fn plan_of_goals(deps: &[(&str, &[&str])]) -> Battleplan {
+        let mut text = String::new();
+        for &(id, goal_deps) in deps {
+            text.push_str(&format!("\
+- id: {}
+  goal: Goal {}
+  pitch: A pitch
+  theme: a-theme
+  tracking-link: https://github.com/rust-lang/rust/issues/1
+  release: a-release
+  goal-deps: [{}]
+", id, id, goal_deps.join(", ")));
+        }
+        let yaml = YamlLoader::load_from_str(&text).unwrap();
+        let src = SourceMap::scan(Path::new("goals.yml"), &text);
+        let mut diags = Vec::new();
+        let goals: Vec<Goal> = objects_from_yaml(yaml, &src, &mut diags).unwrap();
+        assert!(diags.is_empty());
+
+        Battleplan {
+            themes: vec![],
+            goals: goals,
+            problems: vec![],
+            releases: vec![],
+            pipelines: vec![],
+            teams: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    /// The goal each dependency cycle is reported at, with its message
+    fn dep_cycles(plan: &Battleplan) -> Vec<(String, String, usize)> {
+        let mut diags = Vec::new();
+        plan.check_goal_deps(&mut diags);
+        diags.iter()
+            .filter(|d| d.code == "dependency-cycle")
+            .map(|d| (d.obj_id.clone(), d.message.clone(), d.location.line))
+            .collect()
+    }
+
+    #[test]
+    fn acyclic_deps_arent_cycles() {
+        // A diamond reaches `d` twice, but never through itself
+        let plan = plan_of_goals(&[("a", &["b", "c"]), ("b", &["d"]), ("c", &["d"]), ("d", &[])]);
+        assert!(dep_cycles(&plan).is_empty());
+    }
+
+    #[test]
+    fn each_cycle_is_reported_once() {
+        let plan = plan_of_goals(&[
+            ("a", &["b"]),
+            ("b", &["c"]),
+            ("c", &["a"]),
+            // Depends on the cycle without being part of it
+            ("d", &["a"]),
+            ("e", &["e"]),
+        ]);
+
+        assert_eq!(dep_cycles(&plan), vec![
+            ("a".to_string(), "goal a depends on itself: a -> b -> c -> a".to_string(), 7),
+            ("e".to_string(), "goal e depends on itself: e -> e".to_string(), 35),
+        ]);
+    }
+}
@@ -1,48 +1,262 @@
-use {Battleplan, load_plan};
-use chrono::{UTC};
+use {Battleplan, Pipeline, PipelineStageRule, load_plan};
+use chrono::{DateTime, Duration, UTC};
 use errors::*;
-use crawl::{UrlFacts, load_url_facts, FactSetExt};
-use std::collections::HashMap;
+use diag::{Diagnostic, Location};
+use crawl::{UrlFacts, Distance, DEFAULT_MAX_DISTANCE, load_url_facts, parse_gh_issue,
+            parse_issue_links, parse_rfc_numbers, FactSetExt, TASK_PATTERN};
+use std::collections::{HashMap, HashSet, VecDeque};
 use url::Url;
 use regex::Regex;
+use std::iter;
 use std::ops::Deref;
 use std::convert::TryFrom;
+use std::result::Result as StdResult;
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de};
+
+/// Serializes an enum of unit variants as the given strings. serde_yaml
+/// writes unit variants as maps, which the site can't compare against.
+macro_rules! string_enum {
+    ($t:ident { $($variant:ident => $name:tt,)* }) => {
+        impl $t {
+            fn name(&self) -> &'static str {
+                match *self {
+                    $($t::$variant => $name,)*
+                }
+            }
+        }
+
+        impl Serialize for $t {
+            fn serialize<S>(&self, serializer: &mut S) -> StdResult<(), S::Error>
+                where S: Serializer
+            {
+                serializer.serialize_str(self.name())
+            }
+        }
+
+        impl Deserialize for $t {
+            fn deserialize<D>(deserializer: &mut D) -> StdResult<$t, D::Error>
+                where D: Deserializer
+            {
+                let name = String::deserialize(deserializer)?;
+                match &name[..] {
+                    $($name => Ok($t::$variant),)*
+                    _ => Err(de::Error::invalid_value(
+                        &format!("unknown {} `{}`", stringify!($t), name))),
+                }
+            }
+        }
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
 struct Goal {
-    rfc: Option<RfcInfo>,
+    rfcs: Vec<RfcInfo>,
     fcp: Option<Url>,
     completed: bool,
     last_updated: Option<(String, u32)>, // (Y-m-d, days-since-update)
+    // The URL whose activity determined `last_updated`
+    last_updated_url: Option<Url>,
+    staleness: Option<Staleness>,
     pipeline_status: PipelineStatus,
+    tasks: Vec<Task>,
+    activity: ActivityInfo,
+    reactions: ReactionInfo,
+    hotness: Vec<HotnessScore>,
+    roster: Vec<Contributor>,
+    entry_points: Vec<EntryPoint>,
+    timeline: Vec<TimelineEvent>,
+    // Whether any of the goals this one depends on are incomplete, and
+    // which
+    blocked: bool,
+    blocked_on: Vec<String>,
+}
+
+/// An item from a tracking issue's task list
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct Task {
+    desc: String,
+    url: Option<Url>,
+    completed: bool,
+    // 0 for top-level tasks, 1 for their sub-tasks, etc.
+    depth: u32,
+    // The heading the task is listed under
+    section: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct TimelineEvent {
+    date: String, // Y-m-d
+    kind: TimelineEventKind,
+    desc: String,
+    url: Url,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+enum TimelineEventKind {
+    RfcFiled,
+    RfcMerged,
+    TrackingIssueOpened,
+    TrackingIssueClosed,
+    SubTaskClosed,
+    PullMerged,
+    FcpStarted,
+    FcpEnded,
+    Stabilized,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
+enum Staleness {
+    Fresh,
+    Stale,
+    SuperStale,
+}
+
+string_enum!(Staleness {
+    Fresh => "fresh",
+    Stale => "stale",
+    SuperStale => "super-stale",
+});
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct Theme {
+    hotness: Vec<HotnessScore>,
+    roster: Vec<Contributor>,
+    entry_points: Vec<EntryPoint>,
+}
+
+/// The work a team is responsible for, by way of its themes
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct Team {
+    themes: Vec<String>,
+    // In plan order
+    goals: Vec<String>,
+    completed_goals: Vec<String>,
+}
+
+/// An open issue that's a good place for new contributors to start
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct EntryPoint {
+    url: Url,
+    title: String,
+    labels: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct Contributor {
+    login: String,
+    score: u32,
+    // Issues opened
+    issues: u32,
+    // Pull requests opened
+    pulls: u32,
+    // Issues and pull requests assigned
+    assigned: u32,
+    comments: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct HotnessScore {
+    window_days: u32,
+    score: u32,
+    // 1 is the hottest
+    rank: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct ReactionInfo {
+    thumbs_up: u32,
+    thumbs_down: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+struct ActivityInfo {
+    last_human_activity: Option<(String, u32)>, // (Y-m-d, days-since-activity)
+    participants: u32,
+    comments: u32,
+    // Comments in the last fortnight
+    comment_velocity: u32,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
 struct RfcInfo {
     num: u32,
     pr: Url,
+    role: RfcRole,
+    state: RfcState,
+    // Whether rfcbot proposed it for a final comment period. Closed
+    // RFCs often never got that far.
+    entered_fcp: bool,
     completed: bool,
 }
 
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
+enum RfcRole {
+    Primary,
+    Amendment,
+    FollowUp,
+}
+
+string_enum!(RfcRole {
+    Primary => "primary",
+    Amendment => "amendment",
+    FollowUp => "follow-up",
+});
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
+enum RfcState {
+    // The RFC PR wasn't crawled
+    Unknown,
+    Open,
+    Fcp,
+    Accepted,
+    Postponed,
+    Rejected,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
 struct PipelineStatus {
+    // The id of the pipeline kind
+    kind: String,
     completed: (usize, usize),
     stages: Vec<(PipelineStage, String, Option<Url>, bool)>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, Ord, PartialEq, PartialOrd, Hash)]
 enum PipelineStage {
-    RfcFiled,
-    RfcFcp,
-    RfcAccepted,
+    RfcFiled(u32),
+    RfcFcp(u32),
+    RfcAccepted(u32),
+    RfcPostponed(u32),
+    RfcRejected(u32),
     TrackingIssueOpen,
     TrackingTask(String),
     AssociatedPull,
     TrackingIssueFcp,
     TrackingIssueClosed,
+    // A stage defined in pipelines.yml
+    Custom(String),
 }
 
-pub fn ponder() -> Result<()> {
+pub struct PonderConfig {
+    pub hotness_windows: Vec<u32>,
+    pub staleness: StalenessThresholds,
+    // Accounts to leave off rosters, in addition to the known bots
+    pub roster_exclude: Vec<String>,
+    // Whether to include task lists from maintainers' comments
+    pub tasks_from_comments: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct StalenessThresholds {
+    pub stale_days: u32,
+    pub super_stale_days: u32,
+}
+
+pub const DEFAULT_HOTNESS_WINDOWS: &'static [u32] = &[14, 42];
+pub const DEFAULT_STALE_DAYS: u32 = 14;
+pub const DEFAULT_SUPER_STALE_DAYS: u32 = 42;
+
+pub fn ponder(config: PonderConfig) -> Result<()> {
     let plan = load_plan()?;
     plan.validate()?;
 
@@ -52,7 +266,7 @@ pub fn ponder() -> Result<()> {
 
     let mut goals = HashMap::new();
     
-    for (ref goal_id, ref url) in goal_urls {
+    for (ref goal_id, ref url, ref seed_urls) in goal_urls {
         info!("calculating goal for {}", goal_id);
 
         if url_facts.get(url).is_none() {
@@ -60,31 +274,132 @@ pub fn ponder() -> Result<()> {
             continue;
         }
 
-        let rfc_info = get_rfc_info(url_facts, url);
-        let last_updated = get_last_updated(url_facts, url);
-        let pipeline_status = get_pipeline_status(url_facts, url);
+        let mut roots = vec![url.clone()];
+        roots.extend(seed_urls.iter().cloned());
+        let depth = plan.goals.iter()
+            .find(|g| g.id == *goal_id)
+            .and_then(|g| g.crawl_depth)
+            .unwrap_or(DEFAULT_MAX_DISTANCE);
+
+        let rfcs = get_rfc_infos(url_facts, url);
+        let last_updated = get_last_updated(url_facts, &roots, depth)
+            .map(|(d, u)| (date_and_age(&d), u));
+        let pipeline = plan.goals.iter()
+            .find(|g| g.id == *goal_id)
+            .and_then(|g| plan.goal_pipeline(g))
+            .expect("validated goal has a pipeline");
+        let tasks = get_tasks(url_facts, url, config.tasks_from_comments);
+        let (pipeline_status, completed) = get_pipeline_status(url_facts, url, pipeline, &tasks);
+        let activity = get_activity(url_facts, url);
+        let reactions = get_reactions(url_facts, url);
+        let hotness = get_hotness(url_facts, &roots, depth, &config.hotness_windows);
+        let roster = get_roster(url_facts, &roots, depth, &config.roster_exclude);
+        let entry_points = get_entry_points(url_facts, &roots);
+        let timeline = get_timeline(url_facts, &roots, depth);
+        let thresholds = get_staleness_thresholds(&plan, goal_id, config.staleness);
+        let staleness = last_updated.as_ref()
+            .map(|&((_, days), _)| get_staleness(days, thresholds));
+        let (last_updated, last_updated_url) = match last_updated {
+            Some((d, u)) => (Some(d), Some(u)),
+            None => (None, None),
+        };
 
         let goal = Goal {
-            rfc: rfc_info,
+            rfcs: rfcs,
             fcp: None,
-            completed: false,
+            completed: completed,
             last_updated: last_updated,
+            last_updated_url: last_updated_url,
+            staleness: staleness,
             pipeline_status: pipeline_status,
+            tasks: tasks,
+            activity: activity,
+            reactions: reactions,
+            hotness: hotness,
+            roster: roster,
+            entry_points: entry_points,
+            timeline: timeline,
+            blocked: false,
+            blocked_on: Vec::new(),
         };
 
         goals.insert(goal_id.to_string(), goal);
     }
 
+    set_blocked(&plan, &mut goals);
+
+    let themes = get_themes(&plan, &goals, &config.hotness_windows);
+    let teams = get_teams(&plan, &goals);
+
+    rank_hotness(goals.values_mut().map(|g| &mut g.hotness).collect());
+
     super::write_yaml("goals", goals)?;
+    super::write_yaml("themes", themes)?;
+    super::write_yaml("teams", teams)?;
 
     Ok(())
 }
 
-fn goal_urls_from_plan(plan: &Battleplan) -> Vec<(String, Url)> {
+/// Checks goals against their releases, skipping goals the last
+/// ponder found complete. Any other goal targeting a closed release is
+/// an error; whether a goal is overdue is only judged for goals ponder
+/// has seen, since without its output there's nothing to go on.
+pub fn check_release_goals(plan: &Battleplan) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    let goals: Option<HashMap<String, Goal>> = match super::load_yaml("goals") {
+        Ok(goals) => Some(goals),
+        Err(e) => {
+            let loc = Location {
+                file: "_data/gen/goals.yml".to_string(),
+                line: 1,
+                col: 1,
+            };
+            diags.push(Diagnostic::warning(
+                loc, "no-ponder-data", "goal", "*".to_string(),
+                format!("goals weren't checked for being overdue; \
+                         the output of `rsz ponder` is missing or unreadable: {}", e)));
+            None
+        }
+    };
+    let today = UTC::today().naive_utc();
+
+    for goal in &plan.goals {
+        let pondered = goals.as_ref().and_then(|gs| gs.get(&goal.id));
+        if pondered.map_or(false, |g| g.completed) {
+            continue;
+        }
+        let release = match plan.releases.iter().find(|r| r.id == goal.release) {
+            Some(r) => r,
+            None => continue,
+        };
+
+        if !release.future {
+            diags.push(Diagnostic::error(
+                goal.src.field_loc("release"), "closed-release", "goal", goal.id.clone(),
+                format!("goal {} targets release {}, which is no longer taking goals",
+                        goal.id, release.id)));
+        } else if let Some(date) = release.date {
+            if pondered.is_some() && date < today {
+                diags.push(Diagnostic::warning(
+                    goal.src.field_loc("release"), "overdue-goal", "goal", goal.id.clone(),
+                    format!("goal {} targets release {}, due {}, but isn't complete",
+                            goal.id, release.id, date)));
+            }
+        }
+    }
+
+    diags
+}
+
+/// Returns each goal's id, tracking URL and seed URLs
+fn goal_urls_from_plan(plan: &Battleplan) -> Vec<(String, Url, Vec<Url>)> {
     let mut cs = Vec::new();
     for goal in &plan.goals {
+        let seed_urls = goal.seed_links.iter()
+            .filter_map(|l| Url::parse(l).ok())
+            .collect();
         match Url::parse(&goal.tracking_link) {
-            Ok(url) => cs.push((goal.id.clone(), url)),
+            Ok(url) => cs.push((goal.id.clone(), url, seed_urls)),
             Err(_) => (/* bogus link */),
         }
     }
@@ -92,170 +407,958 @@ fn goal_urls_from_plan(plan: &Battleplan) -> Vec<(String, Url)> {
     cs
 }
 
-fn get_rfc_info(url_facts: &UrlFacts, goal_url: &Url) -> Option<RfcInfo> {
-    if url_facts.get(goal_url).is_none() {
-        return None;
+/// Every crawled URL within the goal's crawl depth of `roots`,
+/// including the roots themselves. The crawl graph is shared by every
+/// goal, so following links any further would take in other goals.
+fn get_reachable_urls(url_facts: &UrlFacts, roots: &[Url], depth: Distance) -> Vec<Url> {
+    get_urls_within(url_facts, roots, Some(depth))
+}
+
+/// Every crawled URL at most `max_links` links from `roots`, or any
+/// number if `None`, including the roots themselves
+fn get_urls_within(url_facts: &UrlFacts, roots: &[Url], max_links: Option<u32>) -> Vec<Url> {
+    let mut seen: HashSet<Url> = roots.iter().cloned().collect();
+    let mut queue: VecDeque<(Url, u32)> = roots.iter().map(|u| (u.clone(), 0)).collect();
+    let mut urls = Vec::new();
+
+    while let Some((url, links)) = queue.pop_front() {
+        let follow = max_links.map(|m| links < m).unwrap_or(true);
+        if let Some(facts) = url_facts.get(&url) {
+            for link in facts.links() {
+                if follow && seen.insert(link.clone()) {
+                    queue.push_back((link.clone(), links + 1));
+                }
+            }
+        }
+        urls.push(url);
     }
 
-    let facts = &url_facts[goal_url];
-    let rfc_number;
-    let completed;
+    urls
+}
 
-    if let Some(ref issue) = facts.gh_issue() {
-        let issue_body = issue.body.as_ref().map(Deref::deref).unwrap_or("");
-        let rfc_numbers = parse_rfc_numbers(&issue_body);
+/// Finds every RFC mentioned in the tracking issue. The first one is
+/// taken to be the goal's primary RFC; later ones are amendments if
+/// they are described as such, and follow-ups otherwise.
+fn get_rfc_infos(url_facts: &UrlFacts, goal_url: &Url) -> Vec<RfcInfo> {
+    let issue = match url_facts.get(goal_url).and_then(|f| f.gh_issue()) {
+        Some(i) => i,
+        None => return Vec::new(),
+    };
 
-        if rfc_numbers.len() == 0 {
-            return None;
-        }
+    let issue_body = issue.body.as_ref().map(Deref::deref).unwrap_or("");
+    let mut rfcs: Vec<RfcInfo> = Vec::new();
 
-        if rfc_numbers.len() > 1 {
-            warn!("multiple RFC candidates for {}: {:?}", goal_url, rfc_numbers);
+    for line in issue_body.lines() {
+        for rfc_number in parse_rfc_numbers(line) {
+            if rfcs.iter().any(|r| r.num == rfc_number) {
+                continue;
+            }
+
+            let role = if rfcs.is_empty() {
+                RfcRole::Primary
+            } else if line.to_lowercase().contains("amend") {
+                RfcRole::Amendment
+            } else {
+                RfcRole::FollowUp
+            };
+
+            let rfc_url = Url::parse(&format!("https://github.com/rust-lang/rfcs/pull/{}", rfc_number)).expect("");
+            let state = get_rfc_state(url_facts, &rfc_url);
+            let entered_fcp = rfc_entered_fcp(url_facts, &rfc_url);
+
+            rfcs.push(RfcInfo {
+                num: rfc_number,
+                pr: rfc_url,
+                role: role,
+                state: state,
+                entered_fcp: entered_fcp,
+                completed: state == RfcState::Accepted,
+            });
         }
+    }
+
+    rfcs
+}
+
+/// The URLs where a goal is discussed: its tracking issue and RFCs
+fn get_discussion_urls(url_facts: &UrlFacts, goal_url: &Url) -> Vec<Url> {
+    let mut urls = vec![goal_url.clone()];
+    urls.extend(get_rfc_infos(url_facts, goal_url).into_iter().map(|r| r.pr));
+
+    urls
+}
+
+/// Works out where an RFC is in the process from its crawled PR.
+/// Accepted RFCs are merged; postponed ones are closed with the
+/// `postponed` label.
+fn get_rfc_state(url_facts: &UrlFacts, rfc_url: &Url) -> RfcState {
+    let facts = match url_facts.get(rfc_url) {
+        Some(f) => f,
+        None => return RfcState::Unknown,
+    };
 
-        rfc_number = rfc_numbers[0];
-        // Assume the RFC is completed since this was parsed out
-        // of a tracking issue
-        completed = true;
+    let issue = match facts.gh_issue() {
+        Some(i) => i,
+        None => return RfcState::Unknown,
+    };
+
+    if facts.gh_pull().map(|p| p.merged_at.is_some()).unwrap_or(false) {
+        RfcState::Accepted
+    } else if issue.has_label("postponed") {
+        RfcState::Postponed
+    } else if issue.state == "closed" {
+        RfcState::Rejected
+    } else if issue.has_label("final-comment-period") {
+        RfcState::Fcp
     } else {
-        return None;
+        RfcState::Open
     }
+}
 
-    let rfc_url = Url::parse(&format!("https://github.com/rust-lang/rfcs/pulls/{}", rfc_number)).expect("");
+/// Whether the RFC is in, or has been through, a final comment period,
+/// going by its label and rfcbot's announcements
+fn rfc_entered_fcp(url_facts: &UrlFacts, rfc_url: &Url) -> bool {
+    let facts = match url_facts.get(rfc_url) {
+        Some(f) => f,
+        None => return false,
+    };
 
-    Some(RfcInfo {
-        num: rfc_number,
-        pr: rfc_url,
-        completed: completed,
+    let labelled = facts.gh_issue().map(|i| i.has_label("final-comment-period")).unwrap_or(false);
+    labelled || facts.gh_comments().unwrap_or(&[]).iter().any(|c| {
+        let body = c.body.to_lowercase();
+        is_fcp_start(&body) || is_fcp_end(&body)
     })
 }
 
-fn get_last_updated(url_facts: &UrlFacts, goal_url: &Url) -> Option<(String, u32)> {
-    if url_facts.get(goal_url).is_none() {
-        return None;
+/// Whether a lowercased comment is rfcbot announcing an FCP
+fn is_fcp_start(body: &str) -> bool {
+    body.contains("entering its final comment period")
+}
+
+/// Whether a lowercased comment is rfcbot announcing the end of an FCP
+fn is_fcp_end(body: &str) -> bool {
+    body.contains("final comment period is now complete")
+}
+
+/// Finds the most recent activity on anything reachable from the goal
+/// - the issues, sub-tasks, pull requests and RFCs it links to, and
+/// their comments - along with the URL where it happened. Only people
+/// count: `updated_at` also moves for label edits and bot activity, so
+/// it isn't used.
+fn get_last_updated(url_facts: &UrlFacts, roots: &[Url],
+                    depth: Distance) -> Option<(DateTime<UTC>, Url)> {
+    let mut last_updated: Option<(DateTime<UTC>, Url)> = None;
+
+    for url in get_reachable_urls(url_facts, roots, depth) {
+        let facts = match url_facts.get(&url) {
+            Some(f) => f,
+            None => continue,
+        };
+
+        let mut dates = Vec::new();
+
+        if let Some(issue) = facts.gh_issue() {
+            if !is_bot(&issue.user.login) {
+                dates.push(issue.created_at);
+            }
+            dates.extend(issue.closed_at);
+        }
+
+        if let Some(pull) = facts.gh_pull() {
+            dates.extend(pull.closed_at);
+            dates.extend(pull.merged_at);
+        }
+
+        for comment in facts.gh_comments().unwrap_or(&[]) {
+            if !is_bot(&comment.user.login) {
+                dates.push(comment.updated_at);
+            }
+        }
+
+        if let Some(date) = dates.into_iter().max() {
+            if last_updated.as_ref().map(|&(ref d, _)| date > *d).unwrap_or(true) {
+                last_updated = Some((date, url.clone()));
+            }
+        }
     }
 
-    let facts = &url_facts[goal_url];
+    last_updated
+}
+
+/// Picks the thresholds for a goal. A goal's release overrides its
+/// theme, which overrides the global thresholds.
+fn get_staleness_thresholds(plan: &Battleplan,
+                            goal_id: &str,
+                            global: StalenessThresholds) -> StalenessThresholds {
+    let mut thresholds = global;
+
+    let goal = match plan.goals.iter().find(|g| g.id == goal_id) {
+        Some(g) => g,
+        None => return thresholds,
+    };
+
+    if let Some(theme) = plan.themes.iter().find(|t| t.id == goal.theme) {
+        thresholds.stale_days = theme.stale_days.unwrap_or(thresholds.stale_days);
+        thresholds.super_stale_days = theme.super_stale_days.unwrap_or(thresholds.super_stale_days);
+    }
+    if let Some(release) = plan.releases.iter().find(|r| r.id == goal.release) {
+        thresholds.stale_days = release.stale_days.unwrap_or(thresholds.stale_days);
+        thresholds.super_stale_days = release.super_stale_days.unwrap_or(thresholds.super_stale_days);
+    }
 
-    // TODO: This should probably also consider sub-tasks, and certain other
-    // related URLs as part of the last updated time
+    thresholds
+}
 
-    if let Some(ref issue) = facts.gh_issue() {
-        let date = format!("{}", issue.updated_at.format("%Y-%m-%d"));
-        let days_since_update = (UTC::now() - issue.updated_at).num_days();
-        let days_since_update = u32::try_from(days_since_update).unwrap_or(0);
-        Some((date, days_since_update))
+fn get_staleness(days_since_update: u32, thresholds: StalenessThresholds) -> Staleness {
+    if days_since_update > thresholds.super_stale_days {
+        Staleness::SuperStale
+    } else if days_since_update > thresholds.stale_days {
+        Staleness::Stale
     } else {
-        None
+        Staleness::Fresh
+    }
+}
+
+/// Accounts that post automatically and shouldn't count as activity
+const BOTS: &'static [&'static str] = &["bors", "rust-highfive", "rfcbot", "rust-timer"];
+
+fn is_bot(login: &str) -> bool {
+    BOTS.contains(&login) || login.ends_with("[bot]")
+}
+
+fn date_and_age(date: &DateTime<UTC>) -> (String, u32) {
+    let days_since = (UTC::now() - *date).num_days();
+    let days_since = u32::try_from(days_since).unwrap_or(0);
+    (format!("{}", date.format("%Y-%m-%d")), days_since)
+}
+
+/// Summarizes the human discussion on the goal's tracking issue and RFCs
+fn get_activity(url_facts: &UrlFacts, goal_url: &Url) -> ActivityInfo {
+    let urls = get_discussion_urls(url_facts, goal_url);
+
+    let mut participants = HashSet::new();
+    let mut last_activity = None;
+    let mut comments = 0;
+    let mut comment_velocity = 0;
+    let fortnight_ago = UTC::now() - Duration::days(14);
+
+    for url in &urls {
+        let facts = match url_facts.get(url) {
+            Some(f) => f,
+            None => continue,
+        };
+
+        if let Some(issue) = facts.gh_issue() {
+            if !is_bot(&issue.user.login) {
+                participants.insert(issue.user.login.clone());
+                last_activity = ::std::cmp::max(last_activity, Some(issue.created_at));
+            }
+        }
+
+        for comment in facts.gh_comments().unwrap_or(&[]) {
+            if is_bot(&comment.user.login) {
+                continue;
+            }
+            participants.insert(comment.user.login.clone());
+            last_activity = ::std::cmp::max(last_activity, Some(comment.created_at));
+            comments += 1;
+            if comment.created_at > fortnight_ago {
+                comment_velocity += 1;
+            }
+        }
+    }
+
+    ActivityInfo {
+        last_human_activity: last_activity.as_ref().map(date_and_age),
+        participants: participants.len() as u32,
+        comments: comments,
+        comment_velocity: comment_velocity,
+    }
+}
+
+/// Totals the 👍/👎 reactions on the goal's tracking issue and RFCs
+fn get_reactions(url_facts: &UrlFacts, goal_url: &Url) -> ReactionInfo {
+    let urls = get_discussion_urls(url_facts, goal_url);
+
+    let mut thumbs_up = 0;
+    let mut thumbs_down = 0;
+
+    for url in &urls {
+        let issue = url_facts.get(url).and_then(|f| f.gh_issue());
+        if let Some(reactions) = issue.and_then(|i| i.reactions.as_ref()) {
+            thumbs_up += reactions.plus_one as u32;
+            thumbs_down += reactions.minus_one as u32;
+        }
+    }
+
+    ReactionInfo {
+        thumbs_up: thumbs_up,
+        thumbs_down: thumbs_down,
+    }
+}
+
+// Weights of each kind of event in a goal's hotness score
+const HOTNESS_COMMENT: u32 = 2;
+const HOTNESS_UPDATE: u32 = 1;
+const HOTNESS_PULL: u32 = 5;
+const HOTNESS_REACTION: u32 = 1;
+
+/// Scores the recent activity on everything reachable from a goal,
+/// once per window. Comments, pull requests opening, merging and
+/// closing, and issue updates count if they happened within the
+/// window. Reactions aren't timestamped, so they count for issues that
+/// were updated within the window.
+fn get_hotness(url_facts: &UrlFacts, roots: &[Url], depth: Distance,
+               windows: &[u32]) -> Vec<HotnessScore> {
+    let urls = get_reachable_urls(url_facts, roots, depth);
+    let now = UTC::now();
+
+    windows.iter().map(|&window_days| {
+        let since = now - Duration::days(window_days as i64);
+        let in_window = |d: &DateTime<UTC>| *d > since;
+        let mut score = 0;
+
+        for url in &urls {
+            let facts = match url_facts.get(url) {
+                Some(f) => f,
+                None => continue,
+            };
+
+            if let Some(issue) = facts.gh_issue() {
+                if in_window(&issue.updated_at) {
+                    score += HOTNESS_UPDATE;
+                    if let Some(ref reactions) = issue.reactions {
+                        score += reactions.total_count as u32 * HOTNESS_REACTION;
+                    }
+                }
+            }
+
+            if let Some(pull) = facts.gh_pull() {
+                let events = [Some(pull.created_at), pull.merged_at, pull.closed_at];
+                for event in events.iter().filter_map(|e| e.as_ref()) {
+                    if in_window(event) {
+                        score += HOTNESS_PULL;
+                    }
+                }
+            }
+
+            for comment in facts.gh_comments().unwrap_or(&[]) {
+                if !is_bot(&comment.user.login) && in_window(&comment.created_at) {
+                    score += HOTNESS_COMMENT;
+                }
+            }
+        }
+
+        HotnessScore {
+            window_days: window_days,
+            score: score,
+            rank: 0,
+        }
+    }).collect()
+}
+
+/// Fills in each score's rank relative to the others in the same
+/// window, hottest first. Equal scores share a rank, so the ranks
+/// don't depend on the order the scores come in.
+fn rank_hotness(mut scores: Vec<&mut Vec<HotnessScore>>) {
+    let windows = scores.first().map(|s| s.len()).unwrap_or(0);
+
+    for i in 0..windows {
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|&a, &b| scores[b][i].score.cmp(&scores[a][i].score));
+        let mut last: Option<(u32, u32)> = None;
+        for (pos, idx) in order.into_iter().enumerate() {
+            let score = scores[idx][i].score;
+            let rank = match last {
+                Some((last_score, last_rank)) if last_score == score => last_rank,
+                _ => pos as u32 + 1,
+            };
+            scores[idx][i].rank = rank;
+            last = Some((score, rank));
+        }
+    }
+}
+
+// Weights of each kind of contribution in a contributor's score
+const ROSTER_ISSUE: u32 = 2;
+const ROSTER_PULL: u32 = 3;
+const ROSTER_ASSIGNED: u32 = 2;
+const ROSTER_COMMENT: u32 = 1;
+
+fn roster_entry<'a>(roster: &'a mut HashMap<String, Contributor>,
+                    login: &str) -> &'a mut Contributor {
+    roster.entry(login.to_string()).or_insert_with(|| Contributor {
+        login: login.to_string(),
+        score: 0,
+        issues: 0,
+        pulls: 0,
+        assigned: 0,
+        comments: 0,
+    })
+}
+
+/// Sorts contributors by score, highest first
+fn rank_roster(roster: HashMap<String, Contributor>) -> Vec<Contributor> {
+    let mut roster: Vec<_> = roster.into_iter().map(|(_, mut c)| {
+        c.score = c.issues * ROSTER_ISSUE + c.pulls * ROSTER_PULL
+            + c.assigned * ROSTER_ASSIGNED + c.comments * ROSTER_COMMENT;
+        c
+    }).collect();
+    roster.sort_by(|a, b| (b.score, &a.login).cmp(&(a.score, &b.login)));
+
+    roster
+}
+
+/// Ranks everybody who opened, was assigned to, or commented on
+/// anything reachable from the goal
+fn get_roster(url_facts: &UrlFacts, roots: &[Url], depth: Distance,
+              exclude: &[String]) -> Vec<Contributor> {
+    let mut roster = HashMap::new();
+    let excluded = |login: &str| is_bot(login) || exclude.iter().any(|e| e == login);
+
+    for url in get_reachable_urls(url_facts, roots, depth) {
+        let facts = match url_facts.get(&url) {
+            Some(f) => f,
+            None => continue,
+        };
+
+        if let Some(issue) = facts.gh_issue() {
+            if !excluded(&issue.user.login) {
+                let c = roster_entry(&mut roster, &issue.user.login);
+                if facts.gh_pull().is_some() {
+                    c.pulls += 1;
+                } else {
+                    c.issues += 1;
+                }
+            }
+            for assignee in issue.all_assignees() {
+                if !excluded(&assignee.login) {
+                    roster_entry(&mut roster, &assignee.login).assigned += 1;
+                }
+            }
+        }
+
+        for comment in facts.gh_comments().unwrap_or(&[]) {
+            if !excluded(&comment.user.login) {
+                roster_entry(&mut roster, &comment.user.login).comments += 1;
+            }
+        }
+    }
+
+    rank_roster(roster)
+}
+
+fn merge_rosters<'a, I>(rosters: I) -> Vec<Contributor>
+    where I: Iterator<Item=&'a [Contributor]>
+{
+    let mut merged = HashMap::new();
+
+    for roster in rosters {
+        for c in roster {
+            let m = roster_entry(&mut merged, &c.login);
+            m.issues += c.issues;
+            m.pulls += c.pulls;
+            m.assigned += c.assigned;
+            m.comments += c.comments;
+        }
+    }
+
+    rank_roster(merged)
+}
+
+/// Labels that mark issues as approachable for new contributors
+const ENTRY_POINT_LABELS: &'static [&'static str] = &["E-easy", "E-mentor", "E-help-wanted", "help wanted"];
+
+/// Finds open, contributor-friendly issues linked from the goal's
+/// tracking issue or from the task lists of its sub-issues. Anything
+/// further out is usually about something else.
+fn get_entry_points(url_facts: &UrlFacts, roots: &[Url]) -> Vec<EntryPoint> {
+    let mut entry_points = Vec::new();
+
+    for url in get_urls_within(url_facts, roots, Some(2)) {
+        let issue = match url_facts.get(&url).and_then(|f| f.gh_issue()) {
+            Some(i) => i,
+            None => continue,
+        };
+
+        if issue.state != "open" {
+            continue;
+        }
+
+        let labels: Vec<String> = ENTRY_POINT_LABELS.iter()
+            .filter(|l| issue.has_label(l))
+            .map(|l| l.to_string())
+            .collect();
+
+        if !labels.is_empty() {
+            entry_points.push(EntryPoint {
+                url: url.clone(),
+                title: issue.title.clone(),
+                labels: labels,
+            });
+        }
+    }
+
+    entry_points
+}
+
+/// Builds the history of the goal, oldest first, from everything
+/// reachable from it. FCPs are recognized from the announcements
+/// posted to the discussion, and stabilizations from merged pull
+/// requests titled as such.
+fn get_timeline(url_facts: &UrlFacts, roots: &[Url], depth: Distance) -> Vec<TimelineEvent> {
+    let mut events = Vec::new();
+
+    for url in get_reachable_urls(url_facts, roots, depth) {
+        let facts = match url_facts.get(&url) {
+            Some(f) => f,
+            None => continue,
+        };
+        let issue = match facts.gh_issue() {
+            Some(i) => i,
+            None => continue,
+        };
+
+        let is_rfc = url.path().starts_with("/rust-lang/rfcs/");
+        let is_root = roots.contains(&url);
+        let mut push = |date: &DateTime<UTC>, kind, desc: String| {
+            events.push((date.clone(), kind, desc, url.clone()));
+        };
+
+        if let Some(pull) = facts.gh_pull() {
+            let title = &pull.title;
+            if is_rfc {
+                push(&pull.created_at, TimelineEventKind::RfcFiled,
+                     format!("RFC filed: {}", title));
+                if let Some(ref merged_at) = pull.merged_at {
+                    push(merged_at, TimelineEventKind::RfcMerged,
+                         format!("RFC merged: {}", title));
+                }
+            } else if let Some(ref merged_at) = pull.merged_at {
+                let lower = title.to_lowercase();
+                if lower.starts_with("stabilize") || lower.starts_with("stabilise") {
+                    push(merged_at, TimelineEventKind::Stabilized, title.clone());
+                } else {
+                    push(merged_at, TimelineEventKind::PullMerged,
+                         format!("Merged: {}", title));
+                }
+            }
+        } else if is_root {
+            push(&issue.created_at, TimelineEventKind::TrackingIssueOpened,
+                 format!("Tracking issue opened: {}", issue.title));
+            if let Some(ref closed_at) = issue.closed_at {
+                push(closed_at, TimelineEventKind::TrackingIssueClosed,
+                     format!("Tracking issue closed: {}", issue.title));
+            }
+        } else if let Some(ref closed_at) = issue.closed_at {
+            push(closed_at, TimelineEventKind::SubTaskClosed,
+                 format!("Closed: {}", issue.title));
+        }
+
+        for comment in facts.gh_comments().unwrap_or(&[]) {
+            let body = comment.body.to_lowercase();
+            if is_fcp_start(&body) {
+                push(&comment.created_at, TimelineEventKind::FcpStarted,
+                     format!("FCP started: {}", issue.title));
+            } else if is_fcp_end(&body) {
+                push(&comment.created_at, TimelineEventKind::FcpEnded,
+                     format!("FCP ended: {}", issue.title));
+            }
+        }
+    }
+
+    events.sort();
+
+    events.into_iter().map(|(date, kind, desc, url)| {
+        TimelineEvent {
+            date: format!("{}", date.format("%Y-%m-%d")),
+            kind: kind,
+            desc: desc,
+            url: url,
+        }
+    }).collect()
+}
+
+/// Aggregates goal data per theme
+fn get_themes(plan: &Battleplan,
+              goals: &HashMap<String, Goal>,
+              windows: &[u32]) -> HashMap<String, Theme> {
+    let mut themes = HashMap::new();
+
+    for theme in &plan.themes {
+        let hotness = windows.iter().enumerate().map(|(i, &window_days)| {
+            let score = plan.goals.iter()
+                .filter(|g| g.theme == theme.id)
+                .filter_map(|g| goals.get(&g.id))
+                .map(|g| g.hotness[i].score)
+                .sum();
+
+            HotnessScore {
+                window_days: window_days,
+                score: score,
+                rank: 0,
+            }
+        }).collect();
+
+        let rosters = plan.goals.iter()
+            .filter(|g| g.theme == theme.id)
+            .filter_map(|g| goals.get(&g.id))
+            .map(|g| &g.roster[..]);
+        let roster = merge_rosters(rosters);
+
+        let mut entry_points: Vec<EntryPoint> = Vec::new();
+        let goal_entry_points = plan.goals.iter()
+            .filter(|g| g.theme == theme.id)
+            .filter_map(|g| goals.get(&g.id))
+            .flat_map(|g| g.entry_points.iter());
+        for e in goal_entry_points {
+            if !entry_points.iter().any(|x| x.url == e.url) {
+                entry_points.push(e.clone());
+            }
+        }
+
+        themes.insert(theme.id.clone(), Theme {
+            hotness: hotness,
+            roster: roster,
+            entry_points: entry_points,
+        });
+    }
+
+    rank_hotness(themes.values_mut().map(|t| &mut t.hotness).collect());
+
+    themes
+}
+
+/// A goal is blocked until the goals it depends on are complete.
+/// Goals without crawl data count as incomplete.
+fn set_blocked(plan: &Battleplan, goals: &mut HashMap<String, Goal>) {
+    for goal in &plan.goals {
+        let blocked_on: Vec<String> = goal.goal_deps.iter()
+            .filter(|dep| !goals.get(*dep).map(|g| g.completed).unwrap_or(false))
+            .cloned()
+            .collect();
+
+        if let Some(g) = goals.get_mut(&goal.id) {
+            g.blocked = !blocked_on.is_empty();
+            g.blocked_on = blocked_on;
+        }
     }
 }
 
-fn get_pipeline_status(url_facts: &UrlFacts, url: &Url) -> PipelineStatus {
+fn get_teams(plan: &Battleplan, goals: &HashMap<String, Goal>) -> HashMap<String, Team> {
+    let mut teams = HashMap::new();
+
+    for team in &plan.teams {
+        let themes: Vec<String> = plan.themes.iter()
+            .filter(|t| t.team == team.id)
+            .map(|t| t.id.clone())
+            .collect();
+        let team_goals: Vec<String> = plan.goals.iter()
+            .filter(|g| themes.contains(&g.theme))
+            .map(|g| g.id.clone())
+            .collect();
+        let completed_goals = team_goals.iter()
+            .filter(|id| goals.get(*id).map(|g| g.completed).unwrap_or(false))
+            .cloned()
+            .collect();
+
+        teams.insert(team.id.clone(), Team {
+            themes: themes,
+            goals: team_goals,
+            completed_goals: completed_goals,
+        });
+    }
+
+    teams
+}
+
+/// Works out the goal's progress through its pipeline's stages, and
+/// whether that makes the goal complete
+fn get_pipeline_status(url_facts: &UrlFacts,
+                       url: &Url,
+                       pipeline: &Pipeline,
+                       tasks: &[Task]) -> (PipelineStatus, bool) {
     if url_facts.get(url).is_none() {
-        return PipelineStatus { completed: (0, 0), stages: Vec::new() };
+        let status = PipelineStatus {
+            kind: pipeline.id.clone(),
+            completed: (0, 0),
+            stages: Vec::new(),
+        };
+        return (status, false);
     }
 
     let facts = &url_facts[url];
+    let issue = facts.gh_issue();
 
-    let rfc_info = get_rfc_info(url_facts, url);
+    let rfcs = get_rfc_infos(url_facts, url);
 
     let mut stages = Vec::new();
+    // Whether each of the pipeline's stage rules is complete
+    let mut rules_completed = Vec::new();
 
-    if let Some(ref rfc_info) = rfc_info {
-        stages.push((PipelineStage::RfcFiled, Some(rfc_info.pr.clone()), true));
-        if rfc_info.completed {
-            stages.push((PipelineStage::RfcFcp, Some(rfc_info.pr.clone()), true));
-            stages.push((PipelineStage::RfcAccepted, Some(rfc_info.pr.clone()), true));
-        } else {
-            stages.push((PipelineStage::RfcFcp, Some(rfc_info.pr.clone()), false));
-            stages.push((PipelineStage::RfcAccepted, Some(rfc_info.pr.clone()), false));
-        }
-    }
+    for rule in &pipeline.stages {
+        let first_stage = stages.len();
 
-    if let Some(ref issue) = facts.gh_issue() {
-        stages.push((PipelineStage::TrackingIssueOpen, Some(url.clone()), true));
-        // TODO FCP
-        if let Some(ref body) = issue.body {
-            for (desc, url, completed) in parse_steps_from_issue_body(url_facts, body) {
-                stages.push((PipelineStage::TrackingTask(desc), url, completed));
+        match *rule {
+            PipelineStageRule::Rfcs => {
+                for rfc_info in &rfcs {
+                    push_rfc_stages(&mut stages, rfc_info);
+                }
+            }
+            PipelineStageRule::TrackingIssueOpen => {
+                let url = issue.map(|_| url.clone());
+                stages.push((PipelineStage::TrackingIssueOpen, url, issue.is_some()));
+            }
+            PipelineStageRule::TrackingTasks => {
+                // TODO FCP
+                for task in tasks {
+                    let stage = PipelineStage::TrackingTask(task.desc.clone());
+                    stages.push((stage, task.url.clone(), task.completed));
+                }
+                // TODO Associated Pulls
+            }
+            PipelineStageRule::TrackingIssueClosed => {
+                let completed = issue.map(|i| i.closed_at.is_some()).unwrap_or(false);
+                let url = issue.map(|_| url.clone());
+                stages.push((PipelineStage::TrackingIssueClosed, url, completed));
+            }
+            PipelineStageRule::Label { ref name, ref label } => {
+                let completed = issue.map(|i| i.has_label(label)).unwrap_or(false);
+                let url = issue.map(|_| url.clone());
+                stages.push((PipelineStage::Custom(name.clone()), url, completed));
             }
         }
-        // TODO Associated Pulls
-        let completed = issue.closed_at.is_some();
-        stages.push((PipelineStage::TrackingIssueClosed, Some(url.clone()), completed));
-    } else {
-        stages.push((PipelineStage::TrackingIssueOpen, None, false));
-        stages.push((PipelineStage::TrackingIssueClosed, None, false));
+
+        let completed = stages[first_stage..].iter().all(|&(_, _, c)| c);
+        rules_completed.push((rule.id().to_string(), completed));
     }
 
+    let goal_completed = rules_completed.iter()
+        .filter(|&&(ref id, _)| {
+            pipeline.complete_when.is_empty() || pipeline.complete_when.contains(id)
+        })
+        .all(|&(_, c)| c);
+
+    // e.g. "RFC #1522", "Amendment RFC #1951"
+    let rfc_name = |n: u32| {
+        match rfcs.iter().find(|r| r.num == n).map(|r| r.role) {
+            Some(RfcRole::Amendment) => format!("Amendment RFC #{}", n),
+            Some(RfcRole::FollowUp) => format!("Follow-up RFC #{}", n),
+            _ => format!("RFC #{}", n),
+        }
+    };
+
     let stages = stages.into_iter().map(|(stage, url, completed)| {
         let desc = match stage {
-            PipelineStage::RfcFiled => "RFC filed",
-            PipelineStage::RfcFcp => "RFC entered FCP",
-            PipelineStage::RfcAccepted => "RFC accepted",
-            PipelineStage::TrackingIssueOpen => "Tracking issue opened",
-            PipelineStage::TrackingTask(ref s) => s,
+            PipelineStage::RfcFiled(n) => format!("{} filed", rfc_name(n)),
+            PipelineStage::RfcFcp(n) => format!("{} entered FCP", rfc_name(n)),
+            PipelineStage::RfcAccepted(n) => format!("{} accepted", rfc_name(n)),
+            PipelineStage::RfcPostponed(n) => format!("{} postponed", rfc_name(n)),
+            PipelineStage::RfcRejected(n) => format!("{} closed", rfc_name(n)),
+            PipelineStage::TrackingIssueOpen => "Tracking issue opened".to_string(),
+            PipelineStage::TrackingTask(ref s) => s.to_string(),
             PipelineStage::AssociatedPull => panic!(),
-            PipelineStage::TrackingIssueFcp => "Tracking issue FCP",
-            PipelineStage::TrackingIssueClosed => "Tracking issue closed",
+            PipelineStage::TrackingIssueFcp => "Tracking issue FCP".to_string(),
+            PipelineStage::TrackingIssueClosed => "Tracking issue closed".to_string(),
+            PipelineStage::Custom(ref s) => s.to_string(),
         };
 
-        (stage.clone(), desc.to_string(), url, completed)
+        (stage.clone(), desc, url, completed)
     });
 
     let stages: Vec<_> = stages.collect();
     let completed = stages.iter().filter(|&&(_, _, _, completed)| completed).count();
     let total = stages.len();
 
-    PipelineStatus {
+    let status = PipelineStatus {
+        kind: pipeline.id.clone(),
         completed: (completed, total),
         stages: stages,
+    };
+
+    (status, goal_completed)
+}
+
+fn push_rfc_stages(stages: &mut Vec<(PipelineStage, Option<Url>, bool)>, rfc_info: &RfcInfo) {
+    let n = rfc_info.num;
+    let pr = Some(rfc_info.pr.clone());
+    stages.push((PipelineStage::RfcFiled(n), pr.clone(), true));
+    match rfc_info.state {
+        RfcState::Unknown | RfcState::Open => {
+            stages.push((PipelineStage::RfcFcp(n), pr.clone(), false));
+            stages.push((PipelineStage::RfcAccepted(n), pr.clone(), false));
+        }
+        RfcState::Fcp => {
+            stages.push((PipelineStage::RfcFcp(n), pr.clone(), true));
+            stages.push((PipelineStage::RfcAccepted(n), pr.clone(), false));
+        }
+        RfcState::Accepted => {
+            stages.push((PipelineStage::RfcFcp(n), pr.clone(), true));
+            stages.push((PipelineStage::RfcAccepted(n), pr.clone(), true));
+        }
+        // Closed RFCs end the pipeline where acceptance would be,
+        // and aren't counted as progress
+        RfcState::Postponed => {
+            stages.push((PipelineStage::RfcFcp(n), pr.clone(), rfc_info.entered_fcp));
+            stages.push((PipelineStage::RfcPostponed(n), pr.clone(), false));
+        }
+        RfcState::Rejected => {
+            stages.push((PipelineStage::RfcFcp(n), pr.clone(), rfc_info.entered_fcp));
+            stages.push((PipelineStage::RfcRejected(n), pr.clone(), false));
+        }
     }
 }
 
-fn parse_steps_from_issue_body(_url_facts: &UrlFacts, body: &str)
-                               -> Vec<(String, Option<Url>, bool)>  {
-    let mut steps = Vec::new();
-    
-    let re = Regex::new(r"(\*|-) +\[(.)\] +(.*)").expect("");
+/// Collects the tracking issue's task list, and optionally the task
+/// lists posted in comments by its maintainers - its author and
+/// assignees - who often keep progress updates there
+fn get_tasks(url_facts: &UrlFacts, url: &Url, from_comments: bool) -> Vec<Task> {
+    let facts = match url_facts.get(url) {
+        Some(f) => f,
+        None => return Vec::new(),
+    };
+    let issue = match facts.gh_issue() {
+        Some(i) => i,
+        None => return Vec::new(),
+    };
+    let (org, repo) = match parse_gh_issue(url) {
+        Ok((org, repo, _)) => (org, repo),
+        Err(_) => return Vec::new(),
+    };
 
-    for line in body.lines() {
-        if let Some(cap) = re.captures(line) {
-            let indicator = cap.at(2).expect("");
-            let desc = cap.at(3).expect("");
-            // TODO scan desc for Urls
-            // TODO follow urls to look for completion
-            let completed = !indicator.chars().all(char::is_whitespace);
-            steps.push((desc.to_string(), None, completed));
+    let body = issue.body.as_ref().map(Deref::deref).unwrap_or("");
+    let mut tasks = parse_tasks(&org, &repo, body, None);
+
+    if from_comments {
+        let maintainers: Vec<&str> = iter::once(&issue.user)
+            .chain(issue.all_assignees())
+            .map(|u| &u.login[..])
+            .collect();
+
+        // Maintainers usually re-post their whole checklist with each
+        // update, so only each one's latest checklist counts
+        let mut latest: Vec<(&str, &DateTime<UTC>, Vec<Task>)> = Vec::new();
+        for comment in facts.gh_comments().unwrap_or(&[]) {
+            let login = &comment.user.login[..];
+            if !maintainers.contains(&login) {
+                continue;
+            }
+            let section = format!("Update from {}", login);
+            let comment_tasks = parse_tasks(&org, &repo, &comment.body, Some(section));
+            if comment_tasks.is_empty() {
+                continue;
+            }
+            latest.retain(|&(l, date, _)| l != login || *date > comment.created_at);
+            if !latest.iter().any(|&(l, _, _)| l == login) {
+                latest.push((login, &comment.created_at, comment_tasks));
+            }
+        }
+        latest.sort_by(|a, b| a.1.cmp(b.1));
+
+        // Tasks that are already listed take the latest checked state
+        for (_, _, comment_tasks) in latest {
+            for task in comment_tasks {
+                let same = tasks.iter().position(|t| match (&t.url, &task.url) {
+                    (&Some(ref a), &Some(ref b)) => a == b,
+                    _ => t.desc == task.desc,
+                });
+                match same {
+                    Some(i) => tasks[i].completed = task.completed,
+                    None => tasks.push(task),
+                }
+            }
         }
     }
 
-    steps
+    tasks
 }
 
-fn parse_rfc_numbers(text: &str) -> Vec<u32> {
-    // Match "rust-lang/rfcs#more-than-one-digit"
-    let rfc_ref_re = Regex::new(r"rust-lang/rfcs#(\d+)").expect("");
-    let rfc_url_re = Regex::new(r"https://github.com/rust-lang/rfcs/pull/(\d+)").expect("");
-    let mut rfc_numbers = vec!();
-    
+/// Parses the markdown task lists in `text`, keeping track of their
+/// nesting and the heading they appear under. Items may be bulleted
+/// with `*`, `-` or `+`, or numbered like `1.` or `1)`.
+fn parse_tasks(org: &str, repo: &str, text: &str, section: Option<String>) -> Vec<Task> {
+    let heading_re = Regex::new(r"^\s*#{1,6}\s+(.*?)\s*#*\s*$").expect("");
+    let task_re = Regex::new(TASK_PATTERN).expect("");
+
+    let mut tasks = Vec::new();
+    let mut section = section;
+    // The indentation of each enclosing task
+    let mut indents: Vec<usize> = Vec::new();
+
     for line in text.lines() {
-        if let Some(cap) = rfc_ref_re.captures(line) {
-            let rfc_num_str = cap.at(1).expect("");
-            if let Ok(n) = str::parse(rfc_num_str) {
-                rfc_numbers.push(n);
-            } else {
-                warn!("weird rfc number didn't parse {}", rfc_num_str);
-            }
-        } else if let Some(cap) = rfc_url_re.captures(line) {
-            let rfc_num_str = cap.at(1).expect("");
-            if let Ok(n) = str::parse(rfc_num_str) {
-                rfc_numbers.push(n);
-            } else {
-                warn!("weird rfc number didn't parse {}", rfc_num_str);
+        if let Some(cap) = heading_re.captures(line) {
+            section = Some(cap.at(1).expect("").to_string());
+            indents.clear();
+        } else if let Some(cap) = task_re.captures(line) {
+            let indent = cap.at(1).expect("").replace("\t", "    ").len();
+            let indicator = cap.at(3).expect("");
+            let desc = cap.at(4).expect("");
+
+            while indents.last().map(|&i| i >= indent).unwrap_or(false) {
+                indents.pop();
             }
+            let depth = indents.len() as u32;
+            indents.push(indent);
+
+            // TODO follow urls to look for completion
+            let url = parse_issue_links(org, repo, desc).into_iter().next();
+            let completed = !indicator.chars().all(char::is_whitespace);
+
+            tasks.push(Task {
+                desc: desc.to_string(),
+                url: url,
+                completed: completed,
+                depth: depth,
+                section: section.clone(),
+            });
         }
     }
 
-    rfc_numbers
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_tasks;
+
+    #[test]
+    fn parse_tasks_nests_and_sections() {
+        let text = "\
+Some intro, with - [ ] not a task.
+
+- [x] implement #10
+    - [ ] document it in rust-lang/book#20
+- [ ] stabilize
+
+## Follow-ups ##
+1. [ ] lint for it
+2) [X] fix https://github.com/rust-lang/cargo/pull/30
+\t+ [ ] and #40
+";
+        let tasks = parse_tasks("rust-lang", "rust", text, Some("Tasks".to_string()));
+
+        let summary: Vec<_> = tasks.iter()
+            .map(|t| (&t.desc[..], t.completed, t.depth, t.section.as_ref().map(|s| &s[..])))
+            .collect();
+        assert_eq!(summary, vec![
+            ("implement #10", true, 0, Some("Tasks")),
+            ("document it in rust-lang/book#20", false, 1, Some("Tasks")),
+            ("stabilize", false, 0, Some("Tasks")),
+            ("lint for it", false, 0, Some("Follow-ups")),
+            ("fix https://github.com/rust-lang/cargo/pull/30", true, 0, Some("Follow-ups")),
+            ("and #40", false, 1, Some("Follow-ups")),
+        ]);
+
+        let urls: Vec<_> = tasks.iter()
+            .map(|t| t.url.as_ref().map(|u| u.as_str().to_string()))
+            .collect();
+        assert_eq!(urls, vec![
+            Some("https://github.com/rust-lang/rust/issues/10".to_string()),
+            Some("https://github.com/rust-lang/book/issues/20".to_string()),
+            None,
+            None,
+            Some("https://github.com/rust-lang/cargo/issues/30".to_string()),
+            Some("https://github.com/rust-lang/rust/issues/40".to_string()),
+        ]);
+    }
 }
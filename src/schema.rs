@@ -0,0 +1,303 @@
+//! Schemas for the plan files, used to check items before they're
+//! deserialized and published as JSON Schema for editors
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use chrono::NaiveDate;
+use serde_json::{self, Value};
+use errors::*;
+
+pub enum Type {
+    String,
+    // Non-negative
+    Integer,
+    Bool,
+    // A Y-m-d string
+    Date,
+    Array(Box<Type>),
+    // One of these strings
+    Enum(Vec<String>),
+    Object(Vec<Field>),
+    // Any of these types
+    AnyOf(Vec<Type>),
+}
+
+pub struct Field {
+    pub name: &'static str,
+    pub ty: Type,
+    pub required: bool,
+    pub desc: &'static str,
+}
+
+pub fn required(name: &'static str, ty: Type, desc: &'static str) -> Field {
+    Field { name: name, ty: ty, required: true, desc: desc }
+}
+
+pub fn optional(name: &'static str, ty: Type, desc: &'static str) -> Field {
+    Field { name: name, ty: ty, required: false, desc: desc }
+}
+
+/// The Rust types plan fields can have, and how they look in yaml
+pub trait SchemaType {
+    fn schema_type() -> Type;
+}
+
+impl SchemaType for String {
+    fn schema_type() -> Type { Type::String }
+}
+
+impl SchemaType for u32 {
+    fn schema_type() -> Type { Type::Integer }
+}
+
+impl SchemaType for bool {
+    fn schema_type() -> Type { Type::Bool }
+}
+
+impl SchemaType for NaiveDate {
+    fn schema_type() -> Type { Type::Date }
+}
+
+impl<T: SchemaType> SchemaType for Vec<T> {
+    fn schema_type() -> Type { Type::Array(Box::new(T::schema_type())) }
+}
+
+// Whether the field may be omitted is up to the field
+impl<T: SchemaType> SchemaType for Option<T> {
+    fn schema_type() -> Type { T::schema_type() }
+}
+
+/// The schema of the items of one plan file
+pub struct Schema {
+    // The file name, without extension
+    pub file: &'static str,
+    pub desc: &'static str,
+    pub fields: Vec<Field>,
+}
+
+/// A problem with an item, and the top-level field it's in, if any
+pub struct SchemaError {
+    pub field: Option<String>,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Schema {
+    /// Checks one item of the file, returning every problem
+    pub fn check(&self, item: &Value) -> Vec<SchemaError> {
+        let mut errs = Vec::new();
+        let map = match *item {
+            Value::Object(ref map) => map,
+            _ => {
+                errs.push(SchemaError {
+                    field: None,
+                    code: "not-a-map",
+                    message: "is not a map".to_string(),
+                });
+                return errs;
+            }
+        };
+
+        for field in &self.fields {
+            match map.get(field.name) {
+                Some(v) => {
+                    if let Err(msg) = check_type(&field.ty, v) {
+                        errs.push(SchemaError {
+                            field: Some(field.name.to_string()),
+                            code: "bad-field",
+                            message: format!("`{}` {}", field.name, msg),
+                        });
+                    }
+                }
+                None if field.required => {
+                    errs.push(SchemaError {
+                        field: Some(field.name.to_string()),
+                        code: "bad-field",
+                        message: format!("missing field `{}`", field.name),
+                    });
+                }
+                None => (),
+            }
+        }
+        for key in map.keys() {
+            if self.fields.iter().any(|f| f.name == *key) {
+                continue;
+            }
+            errs.push(SchemaError {
+                field: Some(key.clone()),
+                code: "extra-field",
+                message: format!("unknown field `{}`", key),
+            });
+        }
+
+        errs
+    }
+
+    /// The JSON Schema for the whole file
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = BTreeMap::new();
+        schema.insert("$schema".to_string(),
+                      Value::String("http://json-schema.org/draft-04/schema#".to_string()));
+        schema.insert("title".to_string(), Value::String(format!("{}.yml", self.file)));
+        schema.insert("description".to_string(), Value::String(self.desc.to_string()));
+        schema.insert("type".to_string(), Value::String("array".to_string()));
+        // Unknown fields on an item are only warned about, so the
+        // schema mustn't reject them either
+        schema.insert("items".to_string(), object_schema(&self.fields, true));
+
+        Value::Object(schema)
+    }
+}
+
+fn check_type(ty: &Type, v: &Value) -> ::std::result::Result<(), String> {
+    match (ty, v) {
+        (&Type::String, &Value::String(_)) => Ok(()),
+        (&Type::Integer, &Value::U64(_)) => Ok(()),
+        (&Type::Integer, &Value::I64(i)) if i >= 0 => Ok(()),
+        (&Type::Integer, _) => Err("is not a non-negative integer".to_string()),
+        (&Type::Bool, &Value::Bool(_)) => Ok(()),
+        (&Type::Date, &Value::String(ref s)) => {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|_| ())
+                .map_err(|_| format!("'{}' is not a Y-m-d date", s))
+        }
+        (&Type::Array(ref ty), &Value::Array(ref vs)) => {
+            for v in vs {
+                check_type(ty, v).map_err(|e| format!("has an element that {}", e))?;
+            }
+            Ok(())
+        }
+        (&Type::Enum(ref names), &Value::String(ref s)) => {
+            if names.contains(s) {
+                Ok(())
+            } else {
+                Err(format!("'{}' is not one of {}", s, names.join(", ")))
+            }
+        }
+        (&Type::Object(ref fields), &Value::Object(ref map)) => {
+            for field in fields {
+                match map.get(field.name) {
+                    Some(v) => {
+                        check_type(&field.ty, v)
+                            .map_err(|e| format!("has `{}` that {}", field.name, e))?;
+                    }
+                    None if field.required => {
+                        return Err(format!("is missing `{}`", field.name));
+                    }
+                    None => (),
+                }
+            }
+            for key in map.keys() {
+                if !fields.iter().any(|f| f.name == *key) {
+                    return Err(format!("has unknown field `{}`", key));
+                }
+            }
+            Ok(())
+        }
+        (&Type::AnyOf(ref tys), v) => {
+            if tys.iter().any(|ty| check_type(ty, v).is_ok()) {
+                Ok(())
+            } else {
+                Err(format!("is not {}", type_name(ty)))
+            }
+        }
+        (ty, _) => Err(format!("is not {}", type_name(ty))),
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    match *ty {
+        Type::String => "a string".to_string(),
+        Type::Integer => "a non-negative integer".to_string(),
+        Type::Bool => "a bool".to_string(),
+        Type::Date => "a Y-m-d date".to_string(),
+        Type::Array(_) => "an array".to_string(),
+        Type::Enum(_) => "a string".to_string(),
+        Type::Object(_) => "a map".to_string(),
+        Type::AnyOf(ref tys) => {
+            let names: Vec<String> = tys.iter().map(type_name).collect();
+            names.join(" or ")
+        }
+    }
+}
+
+fn type_schema(ty: &Type) -> Value {
+    let mut schema = BTreeMap::new();
+    let s = |s: &str| Value::String(s.to_string());
+
+    match *ty {
+        Type::String => {
+            schema.insert("type".to_string(), s("string"));
+        }
+        Type::Integer => {
+            schema.insert("type".to_string(), s("integer"));
+            schema.insert("minimum".to_string(), Value::U64(0));
+        }
+        Type::Bool => {
+            schema.insert("type".to_string(), s("boolean"));
+        }
+        Type::Date => {
+            schema.insert("type".to_string(), s("string"));
+            schema.insert("format".to_string(), s("date"));
+        }
+        Type::Array(ref ty) => {
+            schema.insert("type".to_string(), s("array"));
+            schema.insert("items".to_string(), type_schema(ty));
+        }
+        Type::Enum(ref names) => {
+            schema.insert("enum".to_string(),
+                          Value::Array(names.iter().map(|n| s(&n[..])).collect()));
+        }
+        Type::Object(ref fields) => return object_schema(fields, false),
+        Type::AnyOf(ref tys) => {
+            schema.insert("anyOf".to_string(),
+                          Value::Array(tys.iter().map(type_schema).collect()));
+        }
+    }
+
+    Value::Object(schema)
+}
+
+fn object_schema(fields: &[Field], allow_extra: bool) -> Value {
+    let mut properties = BTreeMap::new();
+    for field in fields {
+        let mut schema = match type_schema(&field.ty) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        };
+        schema.insert("description".to_string(), Value::String(field.desc.to_string()));
+        properties.insert(field.name.to_string(), Value::Object(schema));
+    }
+    let required = fields.iter()
+        .filter(|f| f.required)
+        .map(|f| Value::String(f.name.to_string()))
+        .collect();
+
+    let mut schema = BTreeMap::new();
+    schema.insert("type".to_string(), Value::String("object".to_string()));
+    schema.insert("properties".to_string(), Value::Object(properties));
+    schema.insert("required".to_string(), Value::Array(required));
+    schema.insert("additionalProperties".to_string(), Value::Bool(allow_extra));
+
+    Value::Object(schema)
+}
+
+/// Writes `{dir}/{file}.json` for each schema
+pub fn write_schemas(dir: &str, schemas: &[Schema]) -> Result<()> {
+    let dir = PathBuf::from(dir);
+    fs::create_dir_all(&dir)?;
+
+    for schema in schemas {
+        let s = serde_json::to_string_pretty(&schema.to_json_schema())
+            .chain_err(|| format!("encoding schema for {}", schema.file))?;
+        let path = dir.join(format!("{}.json", schema.file));
+        let mut f = File::create(&path)?;
+        writeln!(f, "{}", s)?;
+
+        info!("{} updated", path.display());
+    }
+
+    Ok(())
+}